@@ -0,0 +1,76 @@
+// Measures `find_by_id` on the in-memory store, which compares credential ids in constant time,
+// against a scan that compares them with `==`.
+//
+//     cargo bench --bench credential_lookup
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+use webauthn::cose::{CoseEc2Key, CoseKey};
+use webauthn::requests::Aaguid;
+use webauthn::{Credential, CredentialId, CredentialStore};
+
+const USERS: usize = 1000;
+const CREDENTIALS_PER_USER: usize = 2;
+const ITERATIONS: u32 = 1000;
+
+fn credential(id: CredentialId) -> Credential {
+    Credential {
+        id,
+        public_key: CoseKey::EC2(CoseEc2Key {
+            alg: -7,
+            curve: 1,
+            x: vec![0; 32],
+            y: vec![0; 32],
+        }),
+        counter: 0,
+        aaguid: Aaguid([0; 16]),
+        user_handle: Vec::new(),
+        transports: Vec::new(),
+        created_at: SystemTime::now(),
+        last_used: None,
+        name: None,
+        backup_eligible: false,
+        backup_state: false,
+        resident_key: None,
+    }
+}
+
+fn time<F: FnMut() -> bool>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        assert!(f());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    let mut store: HashMap<String, Vec<Credential>> = HashMap::new();
+    for user in 0..USERS {
+        for n in 0..CREDENTIALS_PER_USER {
+            // Realistic ids of 32 bytes.
+            let id = format!("{:0>32}", format!("{}-{}", user, n));
+            store.save(
+                &user.to_string(),
+                &credential(CredentialId::new(id.into_bytes())),
+            );
+        }
+    }
+    // An id that is not registered, but shares a long prefix with every registered one, so that
+    // the whole store is scanned.
+    let missing = CredentialId::new(format!("{:0>32}", "x").into_bytes());
+
+    let constant_time = time(|| store.find_by_id(&missing).is_none());
+    let plain = time(|| {
+        store
+            .values()
+            .flatten()
+            .find(|c| c.id == missing)
+            .cloned()
+            .is_none()
+    });
+    println!(
+        "find_by_id over {} credentials: {:?} constant time, {:?} with ==",
+        USERS * CREDENTIALS_PER_USER,
+        constant_time,
+        plain
+    );
+}
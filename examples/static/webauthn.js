@@ -1,14 +1,23 @@
 const cose_alg_ECDSA_w_SHA256 = -7;
 const cose_alg_ECDSA_w_SHA512 = -36;
 
+function username() {
+  return encodeURIComponent(document.getElementById("username").value)
+}
+
 function register() {
-  fetch("/challenge/xxx", {method: "POST"})
+  fetch("/register/challenge/" + username(), {method: "POST"})
     .then(res => res.json())
     .then(challenge => {
       console.log("challenge");
       console.log(challenge);
       challenge.publicKey.challenge = fromBase64(challenge.publicKey.challenge);
       challenge.publicKey.user.id = fromBase64(challenge.publicKey.user.id);
+      challenge.publicKey.excludeCredentials = challenge.publicKey.excludeCredentials.map(c =>
+        {
+          c.id = fromBase64(c.id)
+          return c
+        })
       return navigator.credentials.create(challenge)
     .then(newCredential => {
       console.log("PublicKeyCredential Created");
@@ -20,7 +29,12 @@ function register() {
       cc.response = {};
       cc.response.attestationObject = toBase64(newCredential.response.attestationObject);
       cc.response.clientDataJSON = toBase64(newCredential.response.clientDataJSON);
+      if (newCredential.response.getTransports) {
+        cc.response.transports = newCredential.response.getTransports();
+      }
       cc.type = newCredential.type;
+      cc.authenticatorAttachment = newCredential.authenticatorAttachment;
+      cc.clientExtensionResults = newCredential.getClientExtensionResults();
       console.log(cc);
       return fetch("/register", {method: "POST", body: JSON.stringify(cc)})
     })
@@ -29,31 +43,34 @@ function register() {
 }
 
 function login() {
-  fetch("/challenge/xxx", {method: "POST"})
+  fetch("/login/challenge/" + username(), {method: "POST"})
     .then(res => res.json())
-    .then(challenge => {
-      console.log("challenge");
-      console.log(challenge);
-      const req = {};
-      req.publicKey = {};
-      req.publicKey.challenge = fromBase64(challenge.publicKey.challenge);
-      req.publicKey.timeout = 6000;
-      req.publicKey.allowCredentials = challenge.publicKey.allowCredentials.map(c =>
+    .then(req => {
+      console.log("req");
+      console.log(req);
+      req.publicKey.challenge = fromBase64(req.publicKey.challenge);
+      req.publicKey.allowCredentials = req.publicKey.allowCredentials.map(c =>
         {
           c.id = fromBase64(c.id)
           return c
         })
-      console.log("req");
-      console.log(req);
       return navigator.credentials.get(req)
     .then(credentials => {
       console.log("PublicKeyCredential Get");
       console.log(credentials);
       const req = {};
+      req.id = credentials.id;
+      req.rawId = toBase64(credentials.rawId);
+      req.type = credentials.type;
+      req.authenticatorAttachment = credentials.authenticatorAttachment;
+      req.clientExtensionResults = credentials.getClientExtensionResults();
       req.response = {};
       req.response.authenticatorData = toBase64(credentials.response.authenticatorData);
       req.response.clientDataJSON = toBase64(credentials.response.clientDataJSON);
       req.response.signature = toBase64(credentials.response.signature);
+      if (credentials.response.userHandle) {
+        req.response.userHandle = toBase64(credentials.response.userHandle);
+      }
       return fetch("/login", {method: "POST", body: JSON.stringify(req)})
     })
     .catch(err => console.log(err));
@@ -61,10 +78,15 @@ function login() {
 }
 
 
+// WebAuthn fields are exchanged as base64url without padding.
 function toBase64(data) {
   return btoa(String.fromCharCode.apply(null, new Uint8Array(data)))
+    .replace(/\+/g, "-")
+    .replace(/\//g, "_")
+    .replace(/=+$/, "")
 }
 
 function fromBase64(data) {
-  return Uint8Array.from(atob(data), c => c.charCodeAt(0))
+  const base64 = data.replace(/-/g, "+").replace(/_/g, "/")
+  return Uint8Array.from(atob(base64), c => c.charCodeAt(0))
 }
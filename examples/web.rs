@@ -23,94 +23,73 @@ pub fn index() -> std::io::Result<NamedFile> {
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct ChallengeResponse {
-    public_key: PublicKey,
+pub struct CreationOptionsResponse {
+    public_key: webauthn::options::PublicKeyCredentialCreationOptions,
 }
 
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AllowCredentials {
-    #[serde(rename = "type")]
-    type_: String,
-    id: String,
-}
-
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PublicKey {
-    challenge: String,
-    rp: RelyingParty,
-    user: User,
-    pub_key_cred_params: Vec<PubKeyCredParams>,
-    allow_credentials: Vec<AllowCredentials>,
-}
-
-#[derive(Debug, Serialize)]
-pub struct RelyingParty {
-    name: String,
+#[post("/register/challenge/<username>")]
+pub fn register_challenge(
+    w: rocket::State<Mutex<WebAuthn>>,
+    username: String,
+) -> Result<Json<CreationOptionsResponse>, String> {
+    let mut w = w.lock().expect("could not lock state");
+    let options = w
+        .creation_options(
+            username.clone().into_bytes(),
+            username.clone(),
+            username.clone(),
+            None,
+        )
+        .map_err(|e| e.to_string())?;
+    debug!("creation options: {} -> {:?}", username, options);
+    Ok(Json(CreationOptionsResponse {
+        public_key: options,
+    }))
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct User {
-    id: String,
-    name: String,
-    display_name: String,
-}
-
-#[derive(Debug, Serialize)]
-pub struct PubKeyCredParams {
-    #[serde(rename = "type")]
-    type_: String,
-    alg: i8,
+pub struct RequestOptionsResponse {
+    public_key: webauthn::options::PublicKeyCredentialRequestOptions,
 }
 
-#[post("/challenge/<username>")]
-pub fn challenge(w: rocket::State<Mutex<WebAuthn>>, username: String) -> Json<ChallengeResponse> {
+#[post("/login/challenge/<username>")]
+pub fn login_challenge(
+    w: rocket::State<Mutex<WebAuthn>>,
+    username: String,
+) -> Result<Json<RequestOptionsResponse>, String> {
     let mut w = w.lock().expect("could not lock state");
-    let challenge = w.generate_challenge(username.clone());
-    let credentials = w.get_credentials(username.clone());
-    debug!("challenge: {} -> {}", username, challenge);
-    Json(ChallengeResponse {
-        public_key: PublicKey {
-            challenge: challenge.to_string(),
-            rp: RelyingParty {
-                name: w.relying_party(),
-            },
-            user: User {
-                id: username.clone(),
-                name: username.clone(),
-                display_name: username.clone(),
-            },
-            pub_key_cred_params: vec![PubKeyCredParams {
-                type_: "public-key".to_string(),
-                alg: -7,
-            }],
-            allow_credentials: credentials
-                .iter()
-                .map(|c| AllowCredentials {
-                    type_: "public-key".to_string(),
-                    id: c.id.clone(),
-                })
-                .collect(),
-        },
-    })
+    let options = w
+        .request_options(username.clone(), None)
+        .map_err(|e| e.to_string())?;
+    debug!("request options: {} -> {:?}", username, options);
+    Ok(Json(RequestOptionsResponse {
+        public_key: options,
+    }))
 }
 
 #[post("/register", data = "<data>")]
 pub fn register(w: rocket::State<Mutex<WebAuthn>>, data: Json<RegisterRequest>) -> String {
     debug!("register: {:?}", *data);
     let mut w = w.lock().expect("could not lock state");
-    w.register(&*data);
-    "xx".to_string()
+    match w.register(&*data) {
+        Ok(result) => format!(
+            "registered credential {} ({:?})",
+            result.credential.id, result.attestation_type
+        ),
+        Err(e) => format!("registration failed: {}", e),
+    }
 }
 
 #[post("/login", data = "<data>")]
 pub fn login(w: rocket::State<Mutex<WebAuthn>>, data: Json<LoginRequest>) -> String {
     debug!("login: {:?}", *data);
     let mut w = w.lock().expect("could not lock state");
-    w.verify(&*data);
-    "xx".to_string()
+    match w.verify(&*data) {
+        Ok(true) => "login succeeded".to_string(),
+        Ok(false) => "invalid signature".to_string(),
+        Err(e) => format!("login failed: {}", e),
+    }
 }
 
 #[get("/js/webauthn.js")]
@@ -120,8 +99,24 @@ pub fn webauthn_js() -> std::io::Result<NamedFile> {
 
 fn main() {
     env_logger::init();
+    let webauthn = WebAuthnBuilder::new()
+        .rp_id("localhost".to_string())
+        .rp_name("WebAuthn-rs".to_string())
+        .origin("http://localhost:8000".to_string())
+        .build()
+        .expect("invalid webauthn configuration");
     rocket::ignite()
-        .mount("/", routes![index, webauthn_js, challenge, register, login])
-        .manage(Mutex::new(WebAuthn::new("rprprp".to_string())))
+        .mount(
+            "/",
+            routes![
+                index,
+                webauthn_js,
+                register_challenge,
+                register,
+                login_challenge,
+                login
+            ],
+        )
+        .manage(Mutex::new(webauthn))
         .launch();
 }
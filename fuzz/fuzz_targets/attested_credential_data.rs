@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryFrom;
+use webauthn::requests::DecodedAuthData;
+
+// Attested credential data is only reachable through the authenticator data, so the input is put
+// behind a header that has the attested credential data flag set, and the extension data flag
+// too for inputs with an odd first byte.
+fuzz_target!(|data: &[u8]| {
+    let mut auth_data = vec![0; 32];
+    let flags = match data.first() {
+        Some(b) if b & 1 == 1 => 0xc1,
+        _ => 0x41,
+    };
+    auth_data.push(flags);
+    auth_data.extend_from_slice(&[0; 4]);
+    auth_data.extend_from_slice(data.get(1..).unwrap_or_default());
+    let _ = DecodedAuthData::try_from(auth_data.as_slice());
+});
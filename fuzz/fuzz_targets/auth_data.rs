@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryFrom;
+use webauthn::requests::DecodedAuthData;
+
+// Authenticator data as sent by the client. Any input may be rejected, but none may panic.
+fuzz_target!(|data: &[u8]| {
+    let _ = DecodedAuthData::try_from(data);
+});
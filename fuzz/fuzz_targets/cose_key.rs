@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use std::convert::TryFrom;
+use webauthn::cose::CoseKey;
+
+// Credential public keys, including their conversion for OpenSSL.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(key) = CoseKey::try_from(data) {
+        let _ = key.to_spki_der();
+        let _ = key.verify_signature(&[], &[]);
+    }
+});
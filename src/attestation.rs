@@ -0,0 +1,1210 @@
+// Verification of attestation statements, dispatched on the attestation statement format.
+// See https://w3c.github.io/webauthn/#sctn-defined-attestation-formats
+
+use crate::{cose, der, requests, tpm, TrustAnchorStore, WebAuthnError};
+use openssl::{bn, ecdsa, hash, nid, sha, x509};
+use std::collections::BTreeMap;
+
+// The certificate chain an attestation statement was signed with, leaf first.
+pub type TrustPath = Vec<x509::X509>;
+
+// See https://w3c.github.io/webauthn/#sctn-attestation-types
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttestationType {
+    None,
+    Self_,
+    Basic(TrustPath),
+    // Attested by an attestation CA that issued a certificate for this authenticator only.
+    AttCA(TrustPath),
+    // Attested by an anonymization CA that issued a certificate for this credential only.
+    AnonCA(TrustPath),
+    // The statement was verified, but which of the above applies could not be told apart.
+    Uncertain(TrustPath),
+}
+
+impl AttestationType {
+    // The certificate chain of the attestation, leaf first, if it has one.
+    pub fn trust_path(&self) -> Option<&TrustPath> {
+        match self {
+            AttestationType::None | AttestationType::Self_ => None,
+            AttestationType::Basic(path)
+            | AttestationType::AttCA(path)
+            | AttestationType::AnonCA(path)
+            | AttestationType::Uncertain(path) => Some(path),
+        }
+    }
+}
+
+// What an enterprise attestation tells about the individual authenticator, taken from its
+// attestation certificate. Unlike ordinary attestation certificates, which are shared by many
+// authenticators of a model, this one identifies a single device.
+// See https://w3c.github.io/webauthn/#enterprise
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnterpriseAttestation {
+    pub serial_number: Vec<u8>,
+    // The subject of the certificate as (short attribute name, value) pairs, e.g.
+    // `("serialNumber", "1234")`.
+    pub subject: Vec<(String, String)>,
+}
+
+impl EnterpriseAttestation {
+    pub(crate) fn from_certificate(cert: &x509::X509Ref) -> Result<Self, WebAuthnError> {
+        let serial_number = cert
+            .serial_number()
+            .to_bn()
+            .map_err(WebAuthnError::OpenSSL)?
+            .to_vec();
+        let mut subject = Vec::new();
+        for entry in cert.subject_name().entries() {
+            let name = entry
+                .object()
+                .nid()
+                .short_name()
+                .map_err(WebAuthnError::OpenSSL)?;
+            let value = String::from_utf8_lossy(entry.data().as_slice());
+            subject.push((name.to_string(), value.into_owned()));
+        }
+        Ok(EnterpriseAttestation {
+            serial_number,
+            subject,
+        })
+    }
+}
+
+// Facts specific to an attestation statement format that callers may base trust decisions on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttestationDetails {
+    // From an android-safetynet attestation: whether the device passed the Android compatibility
+    // tests, and when the attestation was created in milliseconds since the epoch.
+    SafetyNet {
+        cts_profile_match: bool,
+        timestamp_ms: u64,
+    },
+    // From an android-key attestation: where the attestation was produced and where the
+    // credential private key lives.
+    AndroidKey {
+        attestation_security_level: AndroidSecurityLevel,
+        keymaster_security_level: AndroidSecurityLevel,
+    },
+}
+
+// Where an Android key is protected.
+// See https://source.android.com/security/keystore/attestation#schema
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AndroidSecurityLevel {
+    Software,
+    // A trusted execution environment (TEE).
+    TrustedEnvironment,
+    // A dedicated hardware security module.
+    StrongBox,
+}
+
+// The outcome of successfully verifying an attestation statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedAttestation {
+    pub attestation_type: AttestationType,
+    pub details: Option<AttestationDetails>,
+}
+
+impl From<AttestationType> for VerifiedAttestation {
+    fn from(attestation_type: AttestationType) -> Self {
+        VerifiedAttestation {
+            attestation_type,
+            details: None,
+        }
+    }
+}
+
+type AttStmt = BTreeMap<serde_cbor::ObjectKey, serde_cbor::Value>;
+
+fn stmt_value<'a>(att_stmt: &'a AttStmt, key: &str) -> Option<&'a serde_cbor::Value> {
+    att_stmt.get(&serde_cbor::ObjectKey::String(key.to_string()))
+}
+
+fn stmt_bytes<'a>(att_stmt: &'a AttStmt, key: &str) -> Result<&'a [u8], WebAuthnError> {
+    stmt_value(att_stmt, key)
+        .and_then(serde_cbor::Value::as_bytes)
+        .map(Vec::as_slice)
+        .ok_or(WebAuthnError::InvalidAttestationStatement)
+}
+
+fn stmt_int(att_stmt: &AttStmt, key: &str) -> Result<i64, WebAuthnError> {
+    stmt_value(att_stmt, key)
+        .and_then(serde_cbor::Value::as_i64)
+        .ok_or(WebAuthnError::InvalidAttestationStatement)
+}
+
+// Parses the `x5c` certificate chain, if present.
+fn stmt_x5c(att_stmt: &AttStmt) -> Result<Option<TrustPath>, WebAuthnError> {
+    let x5c = match stmt_value(att_stmt, "x5c") {
+        Some(x5c) => x5c
+            .as_array()
+            .ok_or(WebAuthnError::InvalidAttestationStatement)?,
+        None => return Ok(None),
+    };
+    let certs = x5c
+        .iter()
+        .map(|cert| {
+            let der = cert
+                .as_bytes()
+                .ok_or(WebAuthnError::InvalidAttestationStatement)?;
+            x509::X509::from_der(der).map_err(WebAuthnError::OpenSSL)
+        })
+        .collect::<Result<TrustPath, WebAuthnError>>()?;
+    if certs.is_empty() {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    Ok(Some(certs))
+}
+
+// Verifies `sig` over `data` with the public key of an attestation certificate.
+fn verify_certificate_signature(
+    cert: &x509::X509Ref,
+    alg: i64,
+    sig: &[u8],
+    data: &[u8],
+) -> Result<(), WebAuthnError> {
+    let key = cert.public_key().map_err(WebAuthnError::OpenSSL)?;
+    let mut verifier = cose::verifier(alg, &key)?;
+    verifier.update(data).map_err(WebAuthnError::OpenSSL)?;
+    if verifier.verify(sig).map_err(WebAuthnError::OpenSSL)? {
+        Ok(())
+    } else {
+        Err(WebAuthnError::InvalidAttestationSignature)
+    }
+}
+
+// Checks that the certificate was issued for the credential public key.
+fn check_certificate_key(
+    cert: &x509::X509Ref,
+    credential_public_key: &cose::CoseKey,
+) -> Result<(), WebAuthnError> {
+    let cert_key = cert.public_key().map_err(WebAuthnError::OpenSSL)?;
+    if cert_key.public_eq(&*credential_public_key.to_pkey()?) {
+        Ok(())
+    } else {
+        Err(WebAuthnError::PublicKeyMismatch)
+    }
+}
+
+// Verifies the attestation statement of a new credential, and that its certificate chain, if any,
+// is trusted by `trust_anchors`. `auth_data` is the raw authenticator data that
+// `decoded_auth_data` was parsed from.
+pub fn verify_attestation(
+    fmt: &str,
+    att_stmt: &serde_cbor::Value,
+    trust_anchors: &TrustAnchorStore,
+    auth_data: &[u8],
+    decoded_auth_data: &requests::DecodedAuthData,
+    client_data_hash: &[u8],
+) -> Result<VerifiedAttestation, WebAuthnError> {
+    let att_stmt = att_stmt
+        .as_object()
+        .ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    let attested_credential_data = decoded_auth_data
+        .attested_credential_data
+        .as_ref()
+        .ok_or(WebAuthnError::MissingAttestedCredentialData)?;
+    // Most formats sign over the concatenation of the authenticator data and the client data hash.
+    let mut verification_data = auth_data.to_vec();
+    verification_data.extend_from_slice(client_data_hash);
+    let attestation = match fmt {
+        "none" => verify_none(att_stmt).map(VerifiedAttestation::from),
+        "packed" => verify_packed(
+            att_stmt,
+            trust_anchors,
+            &verification_data,
+            attested_credential_data,
+        )
+        .map(VerifiedAttestation::from),
+        "fido-u2f" => verify_fido_u2f(
+            att_stmt,
+            trust_anchors,
+            &decoded_auth_data.rpid_hash,
+            client_data_hash,
+            attested_credential_data,
+        )
+        .map(VerifiedAttestation::from),
+        "apple" => verify_apple(
+            att_stmt,
+            trust_anchors,
+            &verification_data,
+            &attested_credential_data.credential_public_key,
+        )
+        .map(VerifiedAttestation::from),
+        "android-safetynet" => {
+            verify_android_safetynet(att_stmt, trust_anchors, &verification_data)
+        }
+        "tpm" => verify_tpm(
+            att_stmt,
+            trust_anchors,
+            &verification_data,
+            attested_credential_data,
+        )
+        .map(VerifiedAttestation::from),
+        "android-key" => verify_android_key(
+            att_stmt,
+            trust_anchors,
+            &verification_data,
+            client_data_hash,
+            &attested_credential_data.credential_public_key,
+        ),
+        _ => Err(WebAuthnError::UnsupportedAttestationFormat(fmt.to_string())),
+    }?;
+    // Without a certificate chain there is nothing that could be trusted.
+    match attestation.attestation_type {
+        AttestationType::None | AttestationType::Self_ if trust_anchors.trust_required() => {
+            Err(WebAuthnError::UntrustedAttestation)
+        }
+        _ => Ok(attestation),
+    }
+}
+
+// The "none" format carries no attestation at all, so there is no trust path to check.
+// See https://w3c.github.io/webauthn/#sctn-none-attestation
+fn verify_none(att_stmt: &AttStmt) -> Result<AttestationType, WebAuthnError> {
+    if att_stmt.is_empty() {
+        Ok(AttestationType::None)
+    } else {
+        Err(WebAuthnError::InvalidAttestationStatement)
+    }
+}
+
+const BASIC_CONSTRAINTS_EXTENSION: &[u64] = &[2, 5, 29, 19];
+// The extension of attestation certificates that holds the AAGUID of the authenticator.
+const FIDO_GEN_CE_AAGUID_EXTENSION: &[u64] = &[1, 3, 6, 1, 4, 1, 45724, 1, 1, 4];
+
+// Checks that a DER encoded attestation certificate is not a CA certificate. BasicConstraints is
+// `SEQUENCE { cA BOOLEAN DEFAULT FALSE, ... }`.
+fn check_not_ca(cert: &[u8]) -> Result<(), WebAuthnError> {
+    if let Some(basic_constraints) = der::find_extension(cert, BASIC_CONSTRAINTS_EXTENSION)? {
+        let (constraints, _) = der::read_tagged(basic_constraints, der::SEQUENCE)?;
+        if let Some((der::BOOLEAN, ca)) = der::elements(constraints)?.first() {
+            if ca.iter().any(|&b| b != 0) {
+                return Err(WebAuthnError::InvalidAttestationStatement);
+            }
+        }
+    }
+    Ok(())
+}
+
+// If a DER encoded attestation certificate names the authenticator model, it must be the
+// attested one.
+fn check_aaguid_extension(cert: &[u8], aaguid: &requests::Aaguid) -> Result<(), WebAuthnError> {
+    if let Some(extension) = der::find_extension(cert, FIDO_GEN_CE_AAGUID_EXTENSION)? {
+        let (cert_aaguid, _) = der::read_tagged(extension, der::OCTET_STRING)?;
+        if cert_aaguid != aaguid.as_bytes() {
+            return Err(WebAuthnError::InvalidAaguid);
+        }
+    }
+    Ok(())
+}
+
+// Checks the requirements on packed attestation certificates. The country, organization and
+// common name of the subject are not checked.
+// See https://w3c.github.io/webauthn/#sctn-packed-attestation-cert-requirements
+fn check_packed_certificate(
+    cert: &x509::X509Ref,
+    aaguid: &requests::Aaguid,
+) -> Result<(), WebAuthnError> {
+    // Attestation certificates must be X.509 version 3, which is encoded as 2.
+    if cert.version() != 2 {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let ou = cert
+        .subject_name()
+        .entries_by_nid(nid::Nid::ORGANIZATIONALUNITNAME)
+        .next()
+        .map(|entry| entry.data().as_slice());
+    if ou != Some(b"Authenticator Attestation") {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let cert = cert.to_der().map_err(WebAuthnError::OpenSSL)?;
+    check_not_ca(&cert)?;
+    check_aaguid_extension(&cert, aaguid)
+}
+
+// See https://w3c.github.io/webauthn/#sctn-packed-attestation
+fn verify_packed(
+    att_stmt: &AttStmt,
+    trust_anchors: &TrustAnchorStore,
+    verification_data: &[u8],
+    attested_credential_data: &requests::AttestedCredentialData,
+) -> Result<AttestationType, WebAuthnError> {
+    let alg = stmt_int(att_stmt, "alg")?;
+    let sig = stmt_bytes(att_stmt, "sig")?;
+    match stmt_x5c(att_stmt)? {
+        Some(x5c) => {
+            check_packed_certificate(&x5c[0], &attested_credential_data.aaguid)?;
+            verify_certificate_signature(&x5c[0], alg, sig, verification_data)?;
+            trust_anchors.verify_chain(&x5c)?;
+            Ok(AttestationType::Basic(x5c))
+        }
+        None => {
+            // Self attestation is signed with the credential private key itself.
+            let credential_public_key = &attested_credential_data.credential_public_key;
+            if alg != credential_public_key.alg() {
+                return Err(WebAuthnError::KeyAlgMismatch);
+            }
+            if credential_public_key.verify_signature(sig, verification_data)? {
+                Ok(AttestationType::Self_)
+            } else {
+                Err(WebAuthnError::InvalidAttestationSignature)
+            }
+        }
+    }
+}
+
+// Attestation by legacy U2F authenticators, which sign the verification data of the U2F raw
+// message format.
+// See https://w3c.github.io/webauthn/#sctn-fido-u2f-attestation
+fn verify_fido_u2f(
+    att_stmt: &AttStmt,
+    trust_anchors: &TrustAnchorStore,
+    rpid_hash: &[u8],
+    client_data_hash: &[u8],
+    attested_credential_data: &requests::AttestedCredentialData,
+) -> Result<AttestationType, WebAuthnError> {
+    let sig = stmt_bytes(att_stmt, "sig")?;
+    let x5c = stmt_x5c(att_stmt)?.ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    if x5c.len() != 1 {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    // Both the attestation certificate and the credential have to be P-256 keys.
+    let cert_key = x5c[0]
+        .public_key()
+        .and_then(|key| key.ec_key())
+        .map_err(|_| WebAuthnError::InvalidAttestationStatement)?;
+    if cert_key.group().curve_name() != Some(nid::Nid::X9_62_PRIME256V1) {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let public_key_u2f = match &attested_credential_data.credential_public_key {
+        cose::CoseKey::EC2(key) if key.curve == cose::EC2_CURVE_P256 => {
+            key.to_uncompressed_point()?
+        }
+        _ => return Err(WebAuthnError::InvalidPublicKey),
+    };
+
+    let mut verification_data = vec![0x00];
+    verification_data.extend_from_slice(rpid_hash);
+    verification_data.extend_from_slice(client_data_hash);
+    verification_data.extend_from_slice(&attested_credential_data.credentialid);
+    verification_data.extend_from_slice(&public_key_u2f);
+    verify_certificate_signature(&x5c[0], cose::ALG_ES256, sig, &verification_data)?;
+    trust_anchors.verify_chain(&x5c)?;
+    Ok(AttestationType::Basic(x5c))
+}
+
+// The extension of Apple credential certificates that holds the nonce.
+const APPLE_NONCE_EXTENSION: &[u64] = &[1, 2, 840, 113635, 100, 8, 2];
+
+// Anonymous attestation by Apple platform authenticators. Instead of signing, Apple issues a
+// certificate for the credential public key that embeds a hash of the verification data. To
+// anchor the chain, add Apple's WebAuthn root CA to the trust anchors.
+// See https://w3c.github.io/webauthn/#sctn-apple-anonymous-attestation
+fn verify_apple(
+    att_stmt: &AttStmt,
+    trust_anchors: &TrustAnchorStore,
+    verification_data: &[u8],
+    credential_public_key: &cose::CoseKey,
+) -> Result<AttestationType, WebAuthnError> {
+    let x5c = stmt_x5c(att_stmt)?.ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    trust_anchors.verify_chain(&x5c)?;
+    let cred_cert = x5c[0].to_der().map_err(WebAuthnError::OpenSSL)?;
+    // The extension value is `SEQUENCE { [1] EXPLICIT OCTET STRING nonce }`.
+    let extension = der::find_extension(&cred_cert, APPLE_NONCE_EXTENSION)?
+        .ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    let (sequence, _) = der::read_tagged(extension, der::SEQUENCE)?;
+    let (tagged, _) = der::read_tagged(sequence, der::context(1))?;
+    let (nonce, _) = der::read_tagged(tagged, der::OCTET_STRING)?;
+    if nonce != sha::sha256(verification_data) {
+        return Err(WebAuthnError::InvalidAttestationSignature);
+    }
+    check_certificate_key(&x5c[0], credential_public_key)?;
+    Ok(AttestationType::AnonCA(x5c))
+}
+
+#[derive(Deserialize)]
+struct JwsHeader {
+    alg: String,
+    x5c: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SafetyNetPayload {
+    nonce: String,
+    cts_profile_match: bool,
+    timestamp_ms: u64,
+}
+
+// Verifies a JWS in compact serialization that is signed with the leaf certificate of the chain in
+// its `x5c` header, and returns the payload along with the chain.
+// See https://tools.ietf.org/html/rfc7515
+pub(crate) fn verify_jws(
+    jws: &str,
+    trust_anchors: &TrustAnchorStore,
+) -> Result<(Vec<u8>, TrustPath), WebAuthnError> {
+    let parts = jws.split('.').collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let header: JwsHeader = serde_json::from_slice(&crate::decode_base64url(parts[0])?)
+        .map_err(|_| WebAuthnError::InvalidAttestationStatement)?;
+    let payload = crate::decode_base64url(parts[1])?;
+    let signature = crate::decode_base64url(parts[2])?;
+
+    // Unlike everywhere else, the certificates in a JWS header are standard base64.
+    let x5c = header
+        .x5c
+        .iter()
+        .map(|cert| {
+            let der = base64::decode(cert).map_err(WebAuthnError::Base64Decode)?;
+            x509::X509::from_der(&der).map_err(WebAuthnError::OpenSSL)
+        })
+        .collect::<Result<TrustPath, WebAuthnError>>()?;
+    if x5c.is_empty() {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    trust_anchors.verify_chain(&x5c)?;
+    // The JWS signature covers the encoded header and payload. ES256 signatures are the raw
+    // concatenation of r and s, which OpenSSL expects DER encoded.
+    // See https://tools.ietf.org/html/rfc7515#section-5.2
+    let signing_input = format!("{}.{}", parts[0], parts[1]);
+    match header.alg.as_str() {
+        "RS256" => verify_certificate_signature(
+            &x5c[0],
+            cose::ALG_RS256,
+            &signature,
+            signing_input.as_bytes(),
+        )?,
+        "ES256" => {
+            if signature.len() != 64 {
+                return Err(WebAuthnError::InvalidAttestationSignature);
+            }
+            let r = bn::BigNum::from_slice(&signature[..32]).map_err(WebAuthnError::OpenSSL)?;
+            let s = bn::BigNum::from_slice(&signature[32..]).map_err(WebAuthnError::OpenSSL)?;
+            let signature = ecdsa::EcdsaSig::from_private_components(r, s)
+                .and_then(|signature| signature.to_der())
+                .map_err(WebAuthnError::OpenSSL)?;
+            verify_certificate_signature(
+                &x5c[0],
+                cose::ALG_ES256,
+                &signature,
+                signing_input.as_bytes(),
+            )?
+        }
+        _ => return Err(WebAuthnError::InvalidAttestationStatement),
+    }
+    Ok((payload, x5c))
+}
+
+// The host name SafetyNet attestation certificates are issued to.
+const SAFETYNET_HOSTNAME: &str = "attest.android.com";
+
+// Attestation by the SafetyNet API of Android devices: the response is a JWS, signed by Google,
+// whose payload includes a hash of the verification data. The certificate chain is in
+// the JWS header; to anchor it, add Google's root CA to the trust anchors.
+// See https://w3c.github.io/webauthn/#sctn-android-safetynet-attestation
+fn verify_android_safetynet(
+    att_stmt: &AttStmt,
+    trust_anchors: &TrustAnchorStore,
+    verification_data: &[u8],
+) -> Result<VerifiedAttestation, WebAuthnError> {
+    stmt_value(att_stmt, "ver")
+        .and_then(serde_cbor::Value::as_string)
+        .ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    let response = std::str::from_utf8(stmt_bytes(att_stmt, "response")?)
+        .map_err(|_| WebAuthnError::InvalidAttestationStatement)?;
+    let (payload, x5c) = verify_jws(response, trust_anchors)?;
+    let payload: SafetyNetPayload =
+        serde_json::from_slice(&payload).map_err(|_| WebAuthnError::InvalidAttestationStatement)?;
+    let issued_to_google = x5c[0]
+        .subject_name()
+        .entries_by_nid(nid::Nid::COMMONNAME)
+        .any(|cn| cn.data().as_slice() == SAFETYNET_HOSTNAME.as_bytes());
+    if !issued_to_google {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+
+    let nonce = base64::decode(&payload.nonce).map_err(WebAuthnError::Base64Decode)?;
+    if nonce != sha::sha256(verification_data) {
+        return Err(WebAuthnError::InvalidAttestationSignature);
+    }
+    Ok(VerifiedAttestation {
+        attestation_type: AttestationType::Basic(x5c),
+        details: Some(AttestationDetails::SafetyNet {
+            cts_profile_match: payload.cts_profile_match,
+            timestamp_ms: payload.timestamp_ms,
+        }),
+    })
+}
+
+// The extension of Android attestation certificates that describes the attested key.
+const ANDROID_KEY_DESCRIPTION_EXTENSION: &[u64] = &[1, 3, 6, 1, 4, 1, 11129, 2, 1, 17];
+// Tags and values of the AuthorizationList entries that are checked.
+const KM_TAG_PURPOSE: u32 = 1;
+const KM_TAG_ALL_APPLICATIONS: u32 = 600;
+const KM_TAG_ORIGIN: u32 = 702;
+const KM_PURPOSE_SIGN: u64 = 2;
+const KM_ORIGIN_GENERATED: u64 = 0;
+
+fn android_security_level(contents: &[u8]) -> Result<AndroidSecurityLevel, WebAuthnError> {
+    match der::read_uint(contents)? {
+        0 => Ok(AndroidSecurityLevel::Software),
+        1 => Ok(AndroidSecurityLevel::TrustedEnvironment),
+        2 => Ok(AndroidSecurityLevel::StrongBox),
+        _ => Err(WebAuthnError::InvalidAttestationStatement),
+    }
+}
+
+// Attestation by the Android keystore, which issues a certificate for the credential public key
+// that describes how the key was created. The key has to be generated in a hardware-backed
+// keystore, usable for signing, and bound to the relying party rather than to all applications.
+// See https://w3c.github.io/webauthn/#sctn-android-key-attestation
+fn verify_android_key(
+    att_stmt: &AttStmt,
+    trust_anchors: &TrustAnchorStore,
+    verification_data: &[u8],
+    client_data_hash: &[u8],
+    credential_public_key: &cose::CoseKey,
+) -> Result<VerifiedAttestation, WebAuthnError> {
+    let alg = stmt_int(att_stmt, "alg")?;
+    let sig = stmt_bytes(att_stmt, "sig")?;
+    let x5c = stmt_x5c(att_stmt)?.ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    verify_certificate_signature(&x5c[0], alg, sig, verification_data)?;
+    check_certificate_key(&x5c[0], credential_public_key)?;
+    trust_anchors.verify_chain(&x5c)?;
+
+    let cred_cert = x5c[0].to_der().map_err(WebAuthnError::OpenSSL)?;
+    let extension = der::find_extension(&cred_cert, ANDROID_KEY_DESCRIPTION_EXTENSION)?
+        .ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    let (key_description, _) = der::read_tagged(extension, der::SEQUENCE)?;
+    let fields = der::elements(key_description)?;
+    if fields.len() < 8 {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    // attestationVersion, attestationSecurityLevel, keymasterVersion, keymasterSecurityLevel,
+    // attestationChallenge, uniqueId, softwareEnforced and teeEnforced, in this order.
+    let expect = |i: usize, tag: u32| match fields[i] {
+        (t, contents) if t == tag => Ok(contents),
+        _ => Err(WebAuthnError::InvalidAttestationStatement),
+    };
+    let attestation_security_level = android_security_level(expect(1, der::ENUMERATED)?)?;
+    let keymaster_security_level = android_security_level(expect(3, der::ENUMERATED)?)?;
+    // A software keystore can be made to attest anything.
+    if attestation_security_level == AndroidSecurityLevel::Software
+        || keymaster_security_level == AndroidSecurityLevel::Software
+    {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    if expect(4, der::OCTET_STRING)? != client_data_hash {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let software_enforced = der::elements(expect(6, der::SEQUENCE)?)?;
+    let tee_enforced = der::elements(expect(7, der::SEQUENCE)?)?;
+    if software_enforced
+        .iter()
+        .chain(tee_enforced.iter())
+        .any(|(tag, _)| *tag == der::context(KM_TAG_ALL_APPLICATIONS))
+    {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+
+    // Only what the secure hardware enforces counts for how the key can be used and where it
+    // comes from.
+    let mut purpose_sign = false;
+    let mut origin_generated = false;
+    for (tag, contents) in tee_enforced {
+        if tag == der::context(KM_TAG_PURPOSE) {
+            let (purposes, _) = der::read_tagged(contents, der::SET)?;
+            for (tag, purpose) in der::elements(purposes)? {
+                if tag == der::INTEGER && der::read_uint(purpose)? == KM_PURPOSE_SIGN {
+                    purpose_sign = true;
+                }
+            }
+        } else if tag == der::context(KM_TAG_ORIGIN) {
+            let (origin, _) = der::read_tagged(contents, der::INTEGER)?;
+            origin_generated = der::read_uint(origin)? == KM_ORIGIN_GENERATED;
+        }
+    }
+    if !purpose_sign || !origin_generated {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    Ok(VerifiedAttestation {
+        attestation_type: AttestationType::Basic(x5c),
+        details: Some(AttestationDetails::AndroidKey {
+            attestation_security_level,
+            keymaster_security_level,
+        }),
+    })
+}
+
+const EXTENDED_KEY_USAGE_EXTENSION: &[u64] = &[2, 5, 29, 37];
+// The extended key usage of TPM attestation identity key certificates.
+const TCG_KP_AIK_CERTIFICATE: &[u64] = &[2, 23, 133, 8, 3];
+
+// Strips leading zero bytes, so that big-endian integers of different widths can be compared.
+fn trim_leading_zeros(v: &[u8]) -> &[u8] {
+    let start = v.iter().position(|&b| b != 0).unwrap_or(v.len());
+    &v[start..]
+}
+
+// Checks that the public area describes the same key as the credential public key.
+fn check_pub_area_key(
+    pub_area: &tpm::PubArea,
+    credential_public_key: &cose::CoseKey,
+) -> Result<(), WebAuthnError> {
+    let matches = match (&pub_area.key, credential_public_key) {
+        (tpm::PublicKey::Rsa { exponent, modulus }, cose::CoseKey::RSA(key)) => {
+            // An exponent of zero stands for the default exponent.
+            let exponent = if *exponent == 0 { 65537 } else { *exponent };
+            trim_leading_zeros(modulus) == trim_leading_zeros(&key.n)
+                && trim_leading_zeros(&exponent.to_be_bytes()) == trim_leading_zeros(&key.e)
+        }
+        (tpm::PublicKey::Ecc { curve, x, y }, cose::CoseKey::EC2(key)) => {
+            let same_curve = match *curve {
+                tpm::TPM_ECC_NIST_P256 => key.curve == cose::EC2_CURVE_P256,
+                tpm::TPM_ECC_NIST_P384 => key.curve == cose::EC2_CURVE_P384,
+                tpm::TPM_ECC_NIST_P521 => key.curve == cose::EC2_CURVE_P521,
+                _ => false,
+            };
+            same_curve && *x == key.x.as_slice() && *y == key.y.as_slice()
+        }
+        _ => false,
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(WebAuthnError::PublicKeyMismatch)
+    }
+}
+
+// Checks the requirements on attestation identity key certificates: they must be X.509 version
+// 3, have an empty subject, be usable for AIK certification and not be a CA. The subject
+// alternative name that identifies the TPM model is not checked.
+// See https://w3c.github.io/webauthn/#sctn-tpm-cert-requirements
+fn check_aik_certificate(
+    cert: &x509::X509Ref,
+    aaguid: &requests::Aaguid,
+) -> Result<(), WebAuthnError> {
+    if cert.version() != 2 || cert.subject_name().entries().count() != 0 {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let cert = cert.to_der().map_err(WebAuthnError::OpenSSL)?;
+
+    let extended_key_usage = der::find_extension(&cert, EXTENDED_KEY_USAGE_EXTENSION)?
+        .ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    let (key_purposes, _) = der::read_tagged(extended_key_usage, der::SEQUENCE)?;
+    let aik_certificate = der::encode_oid(TCG_KP_AIK_CERTIFICATE);
+    if !der::elements(key_purposes)?
+        .into_iter()
+        .any(|(tag, oid)| tag == der::OID && oid == aik_certificate.as_slice())
+    {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+
+    check_not_ca(&cert)?;
+    check_aaguid_extension(&cert, aaguid)
+}
+
+// Attestation by a TPM, e.g. with Windows Hello. The TPM certifies the public area of the
+// credential key with its attestation identity key, including a hash of the verification data.
+// See https://w3c.github.io/webauthn/#sctn-tpm-attestation
+fn verify_tpm(
+    att_stmt: &AttStmt,
+    trust_anchors: &TrustAnchorStore,
+    verification_data: &[u8],
+    attested_credential_data: &requests::AttestedCredentialData,
+) -> Result<AttestationType, WebAuthnError> {
+    let ver = stmt_value(att_stmt, "ver").and_then(serde_cbor::Value::as_string);
+    if ver.map(String::as_str) != Some("2.0") {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let alg = stmt_int(att_stmt, "alg")?;
+    let sig = stmt_bytes(att_stmt, "sig")?;
+    let x5c = stmt_x5c(att_stmt)?.ok_or(WebAuthnError::InvalidAttestationStatement)?;
+    let pub_area_bytes = stmt_bytes(att_stmt, "pubArea")?;
+    let cert_info_bytes = stmt_bytes(att_stmt, "certInfo")?;
+
+    let pub_area = tpm::PubArea::parse(pub_area_bytes)?;
+    check_pub_area_key(&pub_area, &attested_credential_data.credential_public_key)?;
+
+    let cert_info = tpm::CertInfo::parse(cert_info_bytes)?;
+    let digest = cose::digest(alg)?;
+    let expected_extra_data =
+        hash::hash(digest, verification_data).map_err(WebAuthnError::OpenSSL)?;
+    if cert_info.extra_data != &*expected_extra_data {
+        return Err(WebAuthnError::InvalidAttestationSignature);
+    }
+    // The certified key is identified by its name algorithm and the hash of its public area.
+    let name_hash = match pub_area.name_alg {
+        tpm::TPM_ALG_SHA256 => sha::sha256(pub_area_bytes).to_vec(),
+        tpm::TPM_ALG_SHA1 => sha::sha1(pub_area_bytes).to_vec(),
+        _ => return Err(WebAuthnError::InvalidAttestationStatement),
+    };
+    let mut name = pub_area.name_alg.to_be_bytes().to_vec();
+    name.extend_from_slice(&name_hash);
+    if cert_info.attested_name != name.as_slice() {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+
+    verify_certificate_signature(&x5c[0], alg, sig, cert_info_bytes)?;
+    check_aik_certificate(&x5c[0], &attested_credential_data.aaguid)?;
+    trust_anchors.verify_chain(&x5c)?;
+    Ok(AttestationType::AttCA(x5c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use serde_cbor::Value;
+    use std::convert::TryFrom;
+
+    const AAGUID: [u8; 16] = [7; 16];
+    const CLIENT_DATA: &[u8] = br#"{"type":"webauthn.create"}"#;
+    const PACKED_SUBJECT: &[(&str, &str)] = &[
+        ("C", "US"),
+        ("O", "Example"),
+        ("OU", "Authenticator Attestation"),
+        ("CN", "Example Authenticator"),
+    ];
+
+    fn new_credential(key: &TestKey) -> Vec<u8> {
+        let attested = attested_credential_data(AAGUID, b"credential", &key.cose);
+        auth_data(RP_ID, UP | AT, 0, &attested)
+    }
+
+    fn signed_data(auth_data: &[u8]) -> Vec<u8> {
+        let mut v = auth_data.to_vec();
+        v.extend_from_slice(&sha256(CLIENT_DATA));
+        v
+    }
+
+    fn verify(
+        fmt: &str,
+        att_stmt: Value,
+        auth_data: &[u8],
+    ) -> Result<VerifiedAttestation, WebAuthnError> {
+        let decoded = requests::DecodedAuthData::try_from(auth_data).unwrap();
+        verify_attestation(
+            fmt,
+            &att_stmt,
+            &TrustAnchorStore::no_trust_required(),
+            auth_data,
+            &decoded,
+            &sha256(CLIENT_DATA),
+        )
+    }
+
+    fn packed_x5c(
+        extensions: &[(&str, Vec<u8>)],
+        subject: &[(&str, &str)],
+    ) -> Result<(), WebAuthnError> {
+        let ca = TestKey::es256();
+        let att_key = TestKey::es256();
+        let cert = certificate(&att_key, subject, &ca, extensions);
+        let auth_data = new_credential(&TestKey::es256());
+        let att_stmt = cbor_map(vec![
+            (text("alg"), Value::I64(cose::ALG_ES256)),
+            (
+                text("sig"),
+                Value::Bytes(att_key.sign(&signed_data(&auth_data))),
+            ),
+            (text("x5c"), x5c(&[&cert, &root_certificate(&ca)])),
+        ]);
+        let attestation = verify("packed", att_stmt, &auth_data)?;
+        assert!(matches!(
+            attestation.attestation_type,
+            AttestationType::Basic(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn packed_basic() {
+        packed_x5c(&[], PACKED_SUBJECT).unwrap();
+        let aaguid = ("1.3.6.1.4.1.45724.1.1.4", der(0x04, &AAGUID));
+        packed_x5c(&[aaguid], PACKED_SUBJECT).unwrap();
+    }
+
+    #[test]
+    fn packed_certificate_requirements() {
+        let subject = &[("CN", "Example Authenticator")];
+        assert!(matches!(
+            packed_x5c(&[], subject),
+            Err(WebAuthnError::InvalidAttestationStatement)
+        ));
+        let ca = ("2.5.29.19", der(0x30, &der(0x01, &[0xff])));
+        assert!(matches!(
+            packed_x5c(&[ca], PACKED_SUBJECT),
+            Err(WebAuthnError::InvalidAttestationStatement)
+        ));
+        let aaguid = ("1.3.6.1.4.1.45724.1.1.4", der(0x04, &[8; 16]));
+        assert!(matches!(
+            packed_x5c(&[aaguid], PACKED_SUBJECT),
+            Err(WebAuthnError::InvalidAaguid)
+        ));
+    }
+
+    fn packed_self(alg: i64, sig: Vec<u8>) -> Value {
+        cbor_map(vec![
+            (text("alg"), Value::I64(alg)),
+            (text("sig"), Value::Bytes(sig)),
+        ])
+    }
+
+    #[test]
+    fn packed_self_attestation() {
+        for key in &[TestKey::es256(), TestKey::ed25519()] {
+            let auth_data = new_credential(key);
+            let att_stmt = packed_self(key.alg, key.sign(&signed_data(&auth_data)));
+            let attestation = verify("packed", att_stmt.clone(), &auth_data).unwrap();
+            assert!(matches!(
+                attestation.attestation_type,
+                AttestationType::Self_
+            ));
+
+            let decoded = requests::DecodedAuthData::try_from(&auth_data[..]).unwrap();
+            assert!(matches!(
+                verify_attestation(
+                    "packed",
+                    &att_stmt,
+                    &TrustAnchorStore::new(),
+                    &auth_data,
+                    &decoded,
+                    &sha256(CLIENT_DATA),
+                ),
+                Err(WebAuthnError::UntrustedAttestation)
+            ));
+        }
+
+        let key = TestKey::es256();
+        let auth_data = new_credential(&key);
+        let att_stmt = packed_self(key.alg, key.sign(b"something else"));
+        assert!(matches!(
+            verify("packed", att_stmt, &auth_data),
+            Err(WebAuthnError::InvalidAttestationSignature)
+        ));
+    }
+
+    #[test]
+    fn packed_self_alg_mismatch() {
+        let key = TestKey::es256();
+        let auth_data = new_credential(&key);
+        let att_stmt = packed_self(cose::ALG_RS256, key.sign(&signed_data(&auth_data)));
+        assert!(matches!(
+            verify("packed", att_stmt, &auth_data),
+            Err(WebAuthnError::KeyAlgMismatch)
+        ));
+    }
+
+    fn uncompressed_point(key: &TestKey) -> Vec<u8> {
+        let ec_key = key.key.ec_key().unwrap();
+        let mut ctx = bn::BigNumContext::new().unwrap();
+        ec_key
+            .public_key()
+            .to_bytes(
+                ec_key.group(),
+                openssl::ec::PointConversionForm::UNCOMPRESSED,
+                &mut ctx,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn fido_u2f() {
+        let key = TestKey::es256();
+        let att_key = TestKey::es256();
+        let cert = certificate(&att_key, &[("CN", "U2F")], &att_key, &[]);
+        let auth_data = new_credential(&key);
+        let public_key_u2f = uncompressed_point(&key);
+        let mut verification_data = vec![0x00];
+        verification_data.extend_from_slice(&sha256(RP_ID.as_bytes()));
+        verification_data.extend_from_slice(&sha256(CLIENT_DATA));
+        verification_data.extend_from_slice(b"credential");
+        verification_data.extend_from_slice(&public_key_u2f);
+        let att_stmt = cbor_map(vec![
+            (text("sig"), Value::Bytes(att_key.sign(&verification_data))),
+            (text("x5c"), x5c(&[&cert])),
+        ]);
+        let attestation = verify("fido-u2f", att_stmt, &auth_data).unwrap();
+        assert_eq!(
+            attestation.attestation_type,
+            AttestationType::Basic(vec![cert])
+        );
+
+        // U2F signs the raw message format, not the authenticator data.
+        let att_stmt = cbor_map(vec![
+            (
+                text("sig"),
+                Value::Bytes(att_key.sign(&signed_data(&auth_data))),
+            ),
+            (
+                text("x5c"),
+                x5c(&[&certificate(&att_key, &[("CN", "U2F")], &att_key, &[])]),
+            ),
+        ]);
+        assert!(matches!(
+            verify("fido-u2f", att_stmt, &auth_data),
+            Err(WebAuthnError::InvalidAttestationSignature)
+        ));
+    }
+
+    fn apple(
+        credential_key: &TestKey,
+        cert_key: &TestKey,
+        nonce: &[u8],
+    ) -> Result<(), WebAuthnError> {
+        let ca = TestKey::es256();
+        let extension = der(0x30, &der(0xa1, &der(0x04, nonce)));
+        let cert = certificate(
+            cert_key,
+            &[("CN", "Apple credential")],
+            &ca,
+            &[("1.2.840.113635.100.8.2", extension)],
+        );
+        let auth_data = new_credential(credential_key);
+        let att_stmt = cbor_map(vec![(text("x5c"), x5c(&[&cert, &root_certificate(&ca)]))]);
+        let attestation = verify("apple", att_stmt, &auth_data)?;
+        assert!(matches!(
+            attestation.attestation_type,
+            AttestationType::AnonCA(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn apple_anonymous() {
+        let key = TestKey::es256();
+        let nonce = sha256(&signed_data(&new_credential(&key)));
+        apple(&key, &key, &nonce).unwrap();
+        assert!(matches!(
+            apple(&key, &key, &[0; 32]),
+            Err(WebAuthnError::InvalidAttestationSignature)
+        ));
+        assert!(matches!(
+            apple(&key, &TestKey::es256(), &nonce),
+            Err(WebAuthnError::PublicKeyMismatch)
+        ));
+    }
+
+    fn safetynet(
+        auth_data: &[u8],
+        hostname: &str,
+        nonce: &[u8],
+    ) -> Result<VerifiedAttestation, WebAuthnError> {
+        let ca = TestKey::es256();
+        let att_key = TestKey::es256();
+        let cert = certificate(&att_key, &[("CN", hostname)], &ca, &[]);
+        let payload = serde_json::json!({
+            "nonce": base64::encode(nonce),
+            "ctsProfileMatch": true,
+            "timestampMs": 1_600_000_000_000u64,
+        });
+        let jws = es256_jws(&att_key, &[&cert, &root_certificate(&ca)], &payload);
+        let att_stmt = cbor_map(vec![
+            (text("ver"), Value::String("200616037".to_string())),
+            (text("response"), Value::Bytes(jws.into_bytes())),
+        ]);
+        verify("android-safetynet", att_stmt, auth_data)
+    }
+
+    #[test]
+    fn android_safetynet() {
+        let auth_data = new_credential(&TestKey::es256());
+        let nonce = sha256(&signed_data(&auth_data));
+        let attestation = safetynet(&auth_data, SAFETYNET_HOSTNAME, &nonce).unwrap();
+        assert_eq!(
+            attestation.details,
+            Some(AttestationDetails::SafetyNet {
+                cts_profile_match: true,
+                timestamp_ms: 1_600_000_000_000,
+            })
+        );
+        assert!(matches!(
+            safetynet(&auth_data, SAFETYNET_HOSTNAME, &[0; 32]),
+            Err(WebAuthnError::InvalidAttestationSignature)
+        ));
+        assert!(matches!(
+            safetynet(&auth_data, "example.com", &nonce),
+            Err(WebAuthnError::InvalidAttestationStatement)
+        ));
+    }
+
+    // [1] purpose: SET OF INTEGER { sign }, [702] origin: INTEGER generated.
+    fn sign_generated() -> Vec<u8> {
+        let mut v = der(0xa1, &der(0x31, &der(0x02, &[2])));
+        v.extend_from_slice(&[0xbf, 0x85, 0x3e, 0x03]);
+        v.extend_from_slice(&der(0x02, &[0]));
+        v
+    }
+
+    // [600] allApplications: NULL.
+    const ALL_APPLICATIONS: &[u8] = &[0xbf, 0x84, 0x58, 0x02, 0x05, 0x00];
+
+    // A KeyDescription of a key in the TEE, attested at `security_level`, with the given
+    // authorization lists.
+    fn key_description(
+        challenge: &[u8],
+        security_level: u8,
+        software_enforced: &[u8],
+        tee_enforced: &[u8],
+    ) -> Vec<u8> {
+        let integer = |v: u8| der(0x02, &[v]);
+        let enumerated = |v: u8| der(0x0a, &[v]);
+        let mut fields = integer(3);
+        fields.extend_from_slice(&enumerated(security_level));
+        fields.extend_from_slice(&integer(4));
+        fields.extend_from_slice(&enumerated(1));
+        fields.extend_from_slice(&der(0x04, challenge));
+        fields.extend_from_slice(&der(0x04, &[]));
+        fields.extend_from_slice(&der(0x30, software_enforced));
+        fields.extend_from_slice(&der(0x30, tee_enforced));
+        der(0x30, &fields)
+    }
+
+    fn android_key(extension: Vec<u8>) -> Result<VerifiedAttestation, WebAuthnError> {
+        let ca = TestKey::es256();
+        let key = TestKey::es256();
+        let cert = certificate(
+            &key,
+            &[("CN", "Android Keystore Key")],
+            &ca,
+            &[("1.3.6.1.4.1.11129.2.1.17", extension)],
+        );
+        let auth_data = new_credential(&key);
+        let att_stmt = cbor_map(vec![
+            (text("alg"), Value::I64(cose::ALG_ES256)),
+            (
+                text("sig"),
+                Value::Bytes(key.sign(&signed_data(&auth_data))),
+            ),
+            (text("x5c"), x5c(&[&cert, &root_certificate(&ca)])),
+        ]);
+        verify("android-key", att_stmt, &auth_data)
+    }
+
+    #[test]
+    fn android_key_attestation() {
+        let challenge = sha256(CLIENT_DATA);
+        let attestation =
+            android_key(key_description(&challenge, 2, &[], &sign_generated())).unwrap();
+        assert_eq!(
+            attestation.details,
+            Some(AttestationDetails::AndroidKey {
+                attestation_security_level: AndroidSecurityLevel::StrongBox,
+                keymaster_security_level: AndroidSecurityLevel::TrustedEnvironment,
+            })
+        );
+        let mut all_applications = sign_generated();
+        all_applications.extend_from_slice(ALL_APPLICATIONS);
+        for extension in &[
+            key_description(&[0; 32], 2, &[], &sign_generated()),
+            key_description(&challenge, 2, ALL_APPLICATIONS, &sign_generated()),
+            key_description(&challenge, 2, &[], &all_applications),
+            // A software keystore, or authorizations only the software enforces.
+            key_description(&challenge, 0, &[], &sign_generated()),
+            key_description(&challenge, 1, &sign_generated(), &[]),
+        ] {
+            assert!(matches!(
+                android_key(extension.clone()),
+                Err(WebAuthnError::InvalidAttestationStatement)
+            ));
+        }
+    }
+
+    // The TPMT_PUBLIC of a P-256 signing key: type ECC, nameAlg SHA-256, no attributes or policy,
+    // no symmetric algorithm, the ECDSA scheme, curve NIST P-256 and no KDF.
+    fn pub_area(key: &TestKey) -> Vec<u8> {
+        let point = uncompressed_point(key);
+        let mut v = Vec::new();
+        for field in &[0x0023u16, 0x000b] {
+            v.extend_from_slice(&field.to_be_bytes());
+        }
+        v.extend_from_slice(&0u32.to_be_bytes());
+        for field in &[0u16, 0x0010, 0x0018, 0x0003, 0x0010] {
+            v.extend_from_slice(&field.to_be_bytes());
+        }
+        for coordinate in &[&point[1..33], &point[33..]] {
+            v.extend_from_slice(&32u16.to_be_bytes());
+            v.extend_from_slice(coordinate);
+        }
+        v
+    }
+
+    // A TPMS_ATTEST of type TPM_ST_ATTEST_CERTIFY for the key with `pub_area`.
+    fn cert_info(extra_data: &[u8], pub_area: &[u8]) -> Vec<u8> {
+        let mut name = 0x000bu16.to_be_bytes().to_vec();
+        name.extend_from_slice(&sha256(pub_area));
+        let mut v = 0xff54_4347u32.to_be_bytes().to_vec();
+        v.extend_from_slice(&0x8017u16.to_be_bytes());
+        v.extend_from_slice(&0u16.to_be_bytes());
+        v.extend_from_slice(&(extra_data.len() as u16).to_be_bytes());
+        v.extend_from_slice(extra_data);
+        // clockInfo and firmwareVersion.
+        v.extend_from_slice(&[0; 17 + 8]);
+        v.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        v.extend_from_slice(&name);
+        v.extend_from_slice(&0u16.to_be_bytes());
+        v
+    }
+
+    fn tpm(
+        extra_data: Option<&[u8]>,
+        aik_extensions: &[(&str, Vec<u8>)],
+    ) -> Result<VerifiedAttestation, WebAuthnError> {
+        let key = TestKey::es256();
+        let aik = TestKey::es256();
+        let ca = TestKey::es256();
+        let mut extensions = vec![(
+            "2.5.29.37",
+            der(0x30, &der(0x06, &der::encode_oid(TCG_KP_AIK_CERTIFICATE))),
+        )];
+        extensions.extend_from_slice(aik_extensions);
+        let aik_cert = certificate(&aik, &[], &ca, &extensions);
+        let auth_data = new_credential(&key);
+        let pub_area = pub_area(&key);
+        let expected_extra_data = sha256(&signed_data(&auth_data));
+        let cert_info = cert_info(extra_data.unwrap_or(&expected_extra_data), &pub_area);
+        let att_stmt = cbor_map(vec![
+            (text("ver"), Value::String("2.0".to_string())),
+            (text("alg"), Value::I64(cose::ALG_ES256)),
+            (text("sig"), Value::Bytes(aik.sign(&cert_info))),
+            (text("x5c"), x5c(&[&aik_cert, &root_certificate(&ca)])),
+            (text("pubArea"), Value::Bytes(pub_area)),
+            (text("certInfo"), Value::Bytes(cert_info)),
+        ]);
+        verify("tpm", att_stmt, &auth_data)
+    }
+
+    #[test]
+    fn tpm_attestation() {
+        let attestation = tpm(None, &[]).unwrap();
+        assert!(matches!(
+            attestation.attestation_type,
+            AttestationType::AttCA(_)
+        ));
+        assert!(matches!(
+            tpm(Some(&[0; 32]), &[]),
+            Err(WebAuthnError::InvalidAttestationSignature)
+        ));
+    }
+
+    #[test]
+    fn tpm_aik_certificate() {
+        let aaguid = ("1.3.6.1.4.1.45724.1.1.4", der(0x04, &AAGUID));
+        tpm(None, &[aaguid]).unwrap();
+        let aaguid = ("1.3.6.1.4.1.45724.1.1.4", der(0x04, &[8; 16]));
+        assert!(matches!(
+            tpm(None, &[aaguid]),
+            Err(WebAuthnError::InvalidAaguid)
+        ));
+        let ca = ("2.5.29.19", der(0x30, &der(0x01, &[0xff])));
+        assert!(matches!(
+            tpm(None, &[ca]),
+            Err(WebAuthnError::InvalidAttestationStatement)
+        ));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::options::AttestationConveyancePreference;
+use crate::{CredentialStore, TrustAnchorStore, UserVerificationPolicy, WebAuthn, WebAuthnError};
+
+// Configures a `WebAuthn` in one go. Anything that is not set keeps the default of
+// `WebAuthn::new()`.
+//
+//     let webauthn = WebAuthnBuilder::new()
+//         .rp_id("example.com".to_string())
+//         .rp_name("Example".to_string())
+//         .origin("https://example.com".to_string())
+//         .user_verification(UserVerificationPolicy::Required)
+//         .build()?;
+#[derive(Debug, Default)]
+pub struct WebAuthnBuilder {
+    rp_id: Option<String>,
+    rp_name: Option<String>,
+    origins: Vec<String>,
+    timeout_ms: Option<u32>,
+    user_verification: Option<UserVerificationPolicy>,
+    algorithms: Option<Vec<i64>>,
+    attestation: Option<AttestationConveyancePreference>,
+    trust_anchors: Option<TrustAnchorStore>,
+    challenge_size: Option<usize>,
+}
+
+impl WebAuthnBuilder {
+    pub fn new() -> Self {
+        WebAuthnBuilder::default()
+    }
+
+    // The relying party id, i.e. the domain credentials are scoped to. Required.
+    pub fn rp_id(mut self, rp_id: String) -> Self {
+        self.rp_id = Some(rp_id);
+        self
+    }
+
+    // The human-readable relying party name shown by the browser. Defaults to the id.
+    pub fn rp_name(mut self, rp_name: String) -> Self {
+        self.rp_name = Some(rp_name);
+        self
+    }
+
+    // Adds an origin that ceremonies are accepted from. Can be called more than once; defaults
+    // to `https://<rp id>`.
+    pub fn origin(mut self, origin: String) -> Self {
+        self.origins.push(origin);
+        self
+    }
+
+    pub fn timeout_ms(mut self, timeout_ms: u32) -> Self {
+        self.timeout_ms = Some(timeout_ms);
+        self
+    }
+
+    pub fn user_verification(mut self, policy: UserVerificationPolicy) -> Self {
+        self.user_verification = Some(policy);
+        self
+    }
+
+    // The COSE algorithms that credentials may use, in order of preference.
+    pub fn algorithms(mut self, algorithms: Vec<i64>) -> Self {
+        self.algorithms = Some(algorithms);
+        self
+    }
+
+    pub fn attestation(mut self, attestation: AttestationConveyancePreference) -> Self {
+        self.attestation = Some(attestation);
+        self
+    }
+
+    // The roots that attestation certificate chains are validated against.
+    pub fn trust_anchors(mut self, trust_anchors: TrustAnchorStore) -> Self {
+        self.trust_anchors = Some(trust_anchors);
+        self
+    }
+
+    // The number of random bytes in challenges, at least 16.
+    pub fn challenge_size(mut self, size_bytes: usize) -> Self {
+        self.challenge_size = Some(size_bytes);
+        self
+    }
+
+    pub fn build(self) -> Result<WebAuthn, WebAuthnError> {
+        self.build_with_store(std::collections::HashMap::new())
+    }
+
+    pub fn build_with_store<S: CredentialStore>(
+        self,
+        store: S,
+    ) -> Result<WebAuthn<S>, WebAuthnError> {
+        let rp_id = self.rp_id.ok_or(WebAuthnError::MissingRpId)?;
+        let mut webauthn = WebAuthn::with_store(rp_id, store);
+        if let Some(rp_name) = self.rp_name {
+            webauthn.set_rp_name(rp_name);
+        }
+        if !self.origins.is_empty() {
+            webauthn.set_origins(self.origins);
+        }
+        if let Some(timeout_ms) = self.timeout_ms {
+            webauthn.set_timeout_ms(timeout_ms);
+        }
+        if let Some(policy) = self.user_verification {
+            webauthn.set_user_verification_policy(policy);
+        }
+        if let Some(algorithms) = self.algorithms {
+            webauthn.set_supported_algorithms(algorithms);
+        }
+        if let Some(attestation) = self.attestation {
+            webauthn.set_attestation(attestation);
+        }
+        if let Some(trust_anchors) = self.trust_anchors {
+            webauthn.set_trust_anchors(trust_anchors);
+        }
+        if let Some(size_bytes) = self.challenge_size {
+            webauthn.set_challenge_size(size_bytes)?;
+        }
+        Ok(webauthn)
+    }
+}
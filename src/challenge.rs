@@ -1,21 +1,113 @@
-#[derive(Clone)]
+use crate::WebAuthnError;
+
+// The source of the random bytes in challenges. It has to be cryptographically secure, as
+// challenges are what keeps ceremonies from being replayed. On targets without an OS RNG, e.g.
+// `wasm32-unknown-unknown`, implement it with whatever the platform offers. When no entropy is
+// available it should return `RngUnavailable` rather than weaker random bytes.
+pub trait ChallengeRng: std::fmt::Debug + Send + Sync {
+    fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), WebAuthnError>;
+}
+
+// Draws from the OS random number generator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultRng;
+
+impl ChallengeRng for DefaultRng {
+    fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), WebAuthnError> {
+        rand::rngs::OsRng::new()
+            .and_then(|mut rng| rand::RngCore::try_fill_bytes(&mut rng, dest))
+            .map_err(|e| {
+                debug!("OS random number generator failed: {}", e);
+                WebAuthnError::RngUnavailable
+            })
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct Challenge(Vec<u8>);
 
 impl Challenge {
-    pub fn new(size_bytes: usize) -> Self {
-        let data = (0..size_bytes).map(|_| rand::random()).collect::<Vec<u8>>();
-        Challenge(data)
+    pub fn new(size_bytes: usize) -> Result<Self, WebAuthnError> {
+        Challenge::generate(size_bytes, &mut DefaultRng)
+    }
+
+    pub fn generate(size_bytes: usize, rng: &mut dyn ChallengeRng) -> Result<Self, WebAuthnError> {
+        let mut data = vec![0; size_bytes];
+        rng.fill_bytes(&mut data)?;
+        Ok(Challenge(data))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    // Challenges are sent to the browser and echoed back in the client data as base64url.
+    pub fn as_base64url(&self) -> String {
+        crate::encode_base64url(&self.0)
+    }
+
+    pub fn from_base64url(data: &str) -> Result<Self, WebAuthnError> {
+        crate::decode_base64url(data).map(Challenge)
+    }
+}
+
+impl serde::Serialize for Challenge {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.as_base64url())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Challenge {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        Challenge::from_base64url(&data).map_err(serde::de::Error::custom)
     }
 }
 
 impl std::fmt::Display for Challenge {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", base64::encode(&self.0))
+        write!(f, "{}", self.as_base64url())
     }
 }
 
 impl std::fmt::Debug for Challenge {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", base64::encode(&self.0))
+        write!(f, "{}", self.as_base64url())
+    }
+}
+
+// The kind of ceremony a challenge was issued for. A challenge only completes a ceremony of the
+// same kind, so that e.g. a login challenge cannot be used to register a credential.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+// A challenge handed out to a client, remembered together with what the ceremony it was issued
+// for expects until the ceremony completes or the challenge expires.
+#[derive(Debug)]
+pub(crate) struct IssuedChallenge {
+    pub challenge: Challenge,
+    pub ceremony: Ceremony,
+    pub issued_at: std::time::SystemTime,
+    // `None` for a discoverable credential login, where the user is only known once the
+    // authenticator returns its user handle.
+    pub user_id: Option<crate::UserId>,
+    // The user handle a registration was started with.
+    pub user_handle: Option<Vec<u8>>,
+    // The U2F app id a login was requested with, which the authenticator data may be scoped to
+    // instead of the relying party id.
+    pub app_id: Option<String>,
+    pub user_verification: crate::UserVerificationPolicy,
+    // Ids of the credentials the user already had: when registering, they must not be
+    // registered again, and when logging in, one of them has to be used.
+    pub user_credentials: Vec<crate::CredentialId>,
+}
+
+impl IssuedChallenge {
+    // A clock that went backwards since the challenge was issued does not make it expire.
+    pub fn is_expired(&self, now: std::time::SystemTime, timeout: std::time::Duration) -> bool {
+        now.duration_since(self.issued_at).unwrap_or_default() > timeout
     }
 }
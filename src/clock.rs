@@ -0,0 +1,15 @@
+// Where the current time comes from, for challenge expiry and the timestamps of credentials. On
+// targets where `SystemTime::now()` is not available, e.g. `wasm32-unknown-unknown`, implement it
+// on top of the platform's clock, such as `Date.now()`.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> std::time::SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> std::time::SystemTime {
+        std::time::SystemTime::now()
+    }
+}
@@ -0,0 +1,703 @@
+use crate::WebAuthnError;
+use openssl::{bn, ec, ecdsa, hash, nid, pkey, rsa, sign};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+// COSE_Key labels, see https://tools.ietf.org/html/rfc8152#section-7.1
+const KEY_TYPE: i64 = 1;
+const ALGORITHM: i64 = 3;
+// EC2 key parameters, see https://tools.ietf.org/html/rfc8152#section-13.1.1
+const EC2_CURVE: i64 = -1;
+const EC2_X: i64 = -2;
+const EC2_Y: i64 = -3;
+// OKP key parameters, see https://tools.ietf.org/html/rfc8152#section-13.2
+const OKP_CURVE: i64 = -1;
+const OKP_X: i64 = -2;
+// RSA key parameters, see https://tools.ietf.org/html/rfc8230#section-4
+const RSA_N: i64 = -1;
+const RSA_E: i64 = -2;
+
+// See https://tools.ietf.org/html/rfc8152#section-13
+pub const KEY_TYPE_OKP: i64 = 1;
+pub const KEY_TYPE_EC2: i64 = 2;
+pub const KEY_TYPE_RSA: i64 = 3;
+pub const EC2_CURVE_P256: i64 = 1;
+pub const EC2_CURVE_P384: i64 = 2;
+pub const EC2_CURVE_P521: i64 = 3;
+pub const OKP_CURVE_ED25519: i64 = 6;
+// See https://www.iana.org/assignments/cose/cose.xhtml#algorithms
+pub const ALG_EDDSA: i64 = -8;
+pub const ALG_ES256: i64 = -7;
+pub const ALG_ES384: i64 = -35;
+pub const ALG_ES512: i64 = -36;
+pub const ALG_PS256: i64 = -37;
+pub const ALG_PS384: i64 = -38;
+pub const ALG_PS512: i64 = -39;
+pub const ALG_RS256: i64 = -257;
+pub const ALG_RS384: i64 = -258;
+pub const ALG_RS512: i64 = -259;
+
+type CoseMap = BTreeMap<serde_cbor::ObjectKey, serde_cbor::Value>;
+
+fn int_value(map: &CoseMap, label: i64) -> Result<i64, WebAuthnError> {
+    map.get(&serde_cbor::ObjectKey::Integer(label))
+        .and_then(serde_cbor::Value::as_i64)
+        .ok_or(WebAuthnError::InvalidPublicKey)
+}
+
+// The digest that signatures of the algorithm are computed over, for the algorithms that hash
+// the data beforehand.
+pub(crate) fn digest(alg: i64) -> Result<hash::MessageDigest, WebAuthnError> {
+    match alg {
+        ALG_ES256 | ALG_RS256 | ALG_PS256 => Ok(hash::MessageDigest::sha256()),
+        ALG_ES384 | ALG_RS384 | ALG_PS384 => Ok(hash::MessageDigest::sha384()),
+        ALG_ES512 | ALG_RS512 | ALG_PS512 => Ok(hash::MessageDigest::sha512()),
+        _ => Err(WebAuthnError::UnsupportedAlgorithm(alg)),
+    }
+}
+
+// The padding of RSA signatures of the algorithm: RSASSA-PKCS1-v1_5 for RS*, RSASSA-PSS for PS*.
+// See https://tools.ietf.org/html/rfc8230#section-2
+fn rsa_padding(alg: i64) -> Result<rsa::Padding, WebAuthnError> {
+    match alg {
+        ALG_RS256 | ALG_RS384 | ALG_RS512 => Ok(rsa::Padding::PKCS1),
+        ALG_PS256 | ALG_PS384 | ALG_PS512 => Ok(rsa::Padding::PKCS1_PSS),
+        _ => Err(WebAuthnError::KeyAlgMismatch),
+    }
+}
+
+// Sets up the verification of a signature of the algorithm with `key`, for the algorithms that
+// hash the data beforehand. PSS signatures use MGF1 with the same digest, and a salt as long as
+// the digest.
+pub(crate) fn verifier(
+    alg: i64,
+    key: &pkey::PKeyRef<pkey::Public>,
+) -> Result<sign::Verifier<'_>, WebAuthnError> {
+    let digest = digest(alg)?;
+    let mut verifier = sign::Verifier::new(digest, key).map_err(WebAuthnError::OpenSSL)?;
+    if key.id() == pkey::Id::RSA {
+        let padding = rsa_padding(alg)?;
+        verifier
+            .set_rsa_padding(padding)
+            .map_err(WebAuthnError::OpenSSL)?;
+        if padding == rsa::Padding::PKCS1_PSS {
+            verifier
+                .set_rsa_pss_saltlen(sign::RsaPssSaltlen::DIGEST_LENGTH)
+                .map_err(WebAuthnError::OpenSSL)?;
+            verifier
+                .set_rsa_mgf1_md(digest)
+                .map_err(WebAuthnError::OpenSSL)?;
+        }
+    }
+    Ok(verifier)
+}
+
+// The length of each coordinate of a point on the curve.
+fn ec2_coordinate_len(curve: i64) -> Option<usize> {
+    match curve {
+        EC2_CURVE_P256 => Some(32),
+        EC2_CURVE_P384 => Some(48),
+        EC2_CURVE_P521 => Some(66),
+        _ => None,
+    }
+}
+
+fn bytes_value(map: &CoseMap, label: i64) -> Result<Vec<u8>, WebAuthnError> {
+    map.get(&serde_cbor::ObjectKey::Integer(label))
+        .and_then(serde_cbor::Value::as_bytes)
+        .cloned()
+        .ok_or(WebAuthnError::InvalidPublicKey)
+}
+
+// An elliptic curve public key, as found in the attested credential data.
+// See https://w3c.github.io/webauthn/#sctn-encoded-credPubKey-examples
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoseEc2Key {
+    pub alg: i64,
+    pub curve: i64,
+    pub x: Vec<u8>,
+    pub y: Vec<u8>,
+}
+
+// An Edwards curve public key.
+// See https://tools.ietf.org/html/rfc8152#section-13.2
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoseOkpKey {
+    pub alg: i64,
+    pub curve: i64,
+    pub x: Vec<u8>,
+}
+
+// An RSA public key, as produced by e.g. Windows Hello.
+// See https://tools.ietf.org/html/rfc8230#section-4
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoseRsaKey {
+    pub alg: i64,
+    pub n: Vec<u8>,
+    pub e: Vec<u8>,
+}
+
+// A credential public key in any of the supported COSE key types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoseKey {
+    EC2(CoseEc2Key),
+    OKP(CoseOkpKey),
+    RSA(CoseRsaKey),
+}
+
+impl TryFrom<&[u8]> for CoseKey {
+    type Error = WebAuthnError;
+
+    fn try_from(v: &[u8]) -> Result<Self, Self::Error> {
+        let value: serde_cbor::Value =
+            serde_cbor::from_slice(v).map_err(WebAuthnError::CborParse)?;
+        let map = value.as_object().ok_or(WebAuthnError::InvalidPublicKey)?;
+        let key = match int_value(map, KEY_TYPE)? {
+            KEY_TYPE_EC2 => {
+                let curve = int_value(map, EC2_CURVE)?;
+                let len = ec2_coordinate_len(curve).ok_or(WebAuthnError::UnsupportedKeyType)?;
+                let x = bytes_value(map, EC2_X)?;
+                let y = bytes_value(map, EC2_Y)?;
+                // Coordinates are encoded with the full length of the curve, leading zeros
+                // included.
+                if x.len() != len || y.len() != len {
+                    return Err(WebAuthnError::InvalidPublicKey);
+                }
+                CoseKey::EC2(CoseEc2Key {
+                    alg: int_value(map, ALGORITHM)?,
+                    curve,
+                    x,
+                    y,
+                })
+            }
+            KEY_TYPE_OKP => {
+                let curve = int_value(map, OKP_CURVE)?;
+                if curve != OKP_CURVE_ED25519 {
+                    return Err(WebAuthnError::UnsupportedKeyType);
+                }
+                CoseKey::OKP(CoseOkpKey {
+                    alg: int_value(map, ALGORITHM)?,
+                    curve,
+                    x: bytes_value(map, OKP_X)?,
+                })
+            }
+            KEY_TYPE_RSA => CoseKey::RSA(CoseRsaKey {
+                alg: int_value(map, ALGORITHM)?,
+                n: bytes_value(map, RSA_N)?,
+                e: bytes_value(map, RSA_E)?,
+            }),
+            _ => return Err(WebAuthnError::UnsupportedKeyType),
+        };
+        key.check_alg()?;
+        // OpenSSL checks that an EC2 point is on its curve and that an OKP key has the right
+        // length. Rejecting such keys here keeps them from being registered, after which every
+        // login with the credential would fail.
+        key.to_pkey().map_err(|_| WebAuthnError::InvalidPublicKey)?;
+        Ok(key)
+    }
+}
+
+impl CoseKey {
+    pub fn alg(&self) -> i64 {
+        match self {
+            CoseKey::EC2(key) => key.alg,
+            CoseKey::OKP(key) => key.alg,
+            CoseKey::RSA(key) => key.alg,
+        }
+    }
+
+    // The algorithm a key states has to fit its type and curve, so that a signature is never
+    // checked with an algorithm other than the one the key was made for. Algorithms this crate
+    // does not know are left to the relying party's list of supported algorithms, except for RSA
+    // keys, which have to state one of the RSA algorithms implemented here.
+    fn check_alg(&self) -> Result<(), WebAuthnError> {
+        let consistent = match (self.alg(), self) {
+            (ALG_ES256, CoseKey::EC2(key)) => key.curve == EC2_CURVE_P256,
+            (ALG_ES384, CoseKey::EC2(key)) => key.curve == EC2_CURVE_P384,
+            (ALG_ES512, CoseKey::EC2(key)) => key.curve == EC2_CURVE_P521,
+            (alg, CoseKey::RSA(_)) => rsa_padding(alg).is_ok(),
+            (ALG_EDDSA, CoseKey::OKP(key)) => key.curve == OKP_CURVE_ED25519,
+            (ALG_ES256, _) | (ALG_ES384, _) | (ALG_ES512, _) | (ALG_EDDSA, _) => false,
+            (alg, _) if rsa_padding(alg).is_ok() => false,
+            _ => true,
+        };
+        if consistent {
+            Ok(())
+        } else {
+            Err(WebAuthnError::KeyAlgMismatch)
+        }
+    }
+
+    // Converts the key for use with OpenSSL, e.g. to compare it with the key of a certificate.
+    pub(crate) fn to_pkey(&self) -> Result<pkey::PKey<pkey::Public>, WebAuthnError> {
+        match self {
+            CoseKey::EC2(key) => key.to_pkey(),
+            CoseKey::OKP(key) => key.to_pkey(),
+            CoseKey::RSA(key) => key.to_pkey(),
+        }
+    }
+
+    pub fn verify_signature(&self, signature: &[u8], data: &[u8]) -> Result<bool, WebAuthnError> {
+        match self {
+            CoseKey::EC2(key) => key.verify_signature(signature, data),
+            CoseKey::OKP(key) => key.verify_signature(signature, data),
+            CoseKey::RSA(key) => key.verify_signature(signature, data),
+        }
+    }
+
+    // Encodes the key as a DER SubjectPublicKeyInfo, the format most other libraries and tools
+    // accept public keys in.
+    // See https://tools.ietf.org/html/rfc5280#section-4.1.2.7
+    pub fn to_spki_der(&self) -> Result<Vec<u8>, WebAuthnError> {
+        self.to_pkey()?
+            .public_key_to_der()
+            .map_err(WebAuthnError::OpenSSL)
+    }
+
+    // The SubjectPublicKeyInfo as a `-----BEGIN PUBLIC KEY-----` PEM block.
+    pub fn to_spki_pem(&self) -> Result<String, WebAuthnError> {
+        let pem = self
+            .to_pkey()?
+            .public_key_to_pem()
+            .map_err(WebAuthnError::OpenSSL)?;
+        String::from_utf8(pem).map_err(|_| WebAuthnError::InvalidPublicKey)
+    }
+
+    // Encodes the key as a COSE_Key in CTAP2 canonical CBOR, i.e. with the labels sorted
+    // 1, 3, -1, -2, -3, so that the same key always encodes to the same bytes.
+    // See https://fidoalliance.org/specs/fido-v2.0-ps-20190130/fido-client-to-authenticator-protocol-v2.0-ps-20190130.html#ctap2-canonical-cbor-encoding-form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        use serde_cbor::Value;
+        let mut entries = match self {
+            CoseKey::EC2(key) => vec![
+                (KEY_TYPE, Value::I64(KEY_TYPE_EC2)),
+                (ALGORITHM, Value::I64(key.alg)),
+                (EC2_CURVE, Value::I64(key.curve)),
+                (EC2_X, Value::Bytes(key.x.clone())),
+                (EC2_Y, Value::Bytes(key.y.clone())),
+            ],
+            CoseKey::OKP(key) => vec![
+                (KEY_TYPE, Value::I64(KEY_TYPE_OKP)),
+                (ALGORITHM, Value::I64(key.alg)),
+                (OKP_CURVE, Value::I64(key.curve)),
+                (OKP_X, Value::Bytes(key.x.clone())),
+            ],
+            CoseKey::RSA(key) => vec![
+                (KEY_TYPE, Value::I64(KEY_TYPE_RSA)),
+                (ALGORITHM, Value::I64(key.alg)),
+                (RSA_N, Value::Bytes(key.n.clone())),
+                (RSA_E, Value::Bytes(key.e.clone())),
+            ],
+        };
+        entries.sort_by_key(|&(label, _)| (label < 0, label.abs()));
+        // Serializing integers and byte strings into a vector cannot fail.
+        serde_cbor::to_vec(&CanonicalMap(entries)).unwrap_or_default()
+    }
+}
+
+// A CBOR map whose entries are written in the given order, unlike `BTreeMap` which would put
+// negative labels first.
+struct CanonicalMap(Vec<(i64, serde_cbor::Value)>);
+
+impl serde::Serialize for CanonicalMap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (label, value) in &self.0 {
+            map.serialize_entry(label, value)?;
+        }
+        map.end()
+    }
+}
+
+// Keys are stored as their COSE_Key encoding: base64url in human-readable formats such as JSON,
+// a byte string otherwise.
+impl serde::Serialize for CoseKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = self.to_bytes();
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&crate::encode_base64url(&bytes))
+        } else {
+            serializer.serialize_bytes(&bytes)
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for CoseKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = if deserializer.is_human_readable() {
+            let encoded = String::deserialize(deserializer)?;
+            crate::decode_base64url(&encoded).map_err(serde::de::Error::custom)?
+        } else {
+            Vec::from(serde_bytes::ByteBuf::deserialize(deserializer)?)
+        };
+        CoseKey::try_from(bytes.as_slice()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl CoseEc2Key {
+    fn to_pkey(&self) -> Result<pkey::PKey<pkey::Public>, WebAuthnError> {
+        let curve = match self.curve {
+            EC2_CURVE_P256 => nid::Nid::X9_62_PRIME256V1,
+            EC2_CURVE_P384 => nid::Nid::SECP384R1,
+            EC2_CURVE_P521 => nid::Nid::SECP521R1,
+            _ => return Err(WebAuthnError::InvalidPublicKey),
+        };
+        let group = ec::EcGroup::from_curve_name(curve).map_err(WebAuthnError::OpenSSL)?;
+        let x = bn::BigNum::from_slice(&self.x).map_err(WebAuthnError::OpenSSL)?;
+        let y = bn::BigNum::from_slice(&self.y).map_err(WebAuthnError::OpenSSL)?;
+        let ec_key = ec::EcKey::from_public_key_affine_coordinates(&group, &x, &y)
+            .map_err(WebAuthnError::OpenSSL)?;
+        pkey::PKey::from_ec_key(ec_key).map_err(WebAuthnError::OpenSSL)
+    }
+
+    // Encodes the key as an uncompressed point, `0x04 || x || y`, as used by U2F.
+    // See https://fidoalliance.org/specs/fido-u2f-v1.2-ps-20170411/fido-u2f-raw-message-formats-v1.2-ps-20170411.html#registration-response-message-success
+    pub fn to_uncompressed_point(&self) -> Result<Vec<u8>, WebAuthnError> {
+        if self.x.len() != 32 || self.y.len() != 32 {
+            return Err(WebAuthnError::InvalidPublicKey);
+        }
+        let mut point = vec![0x04];
+        point.extend_from_slice(&self.x);
+        point.extend_from_slice(&self.y);
+        Ok(point)
+    }
+
+    // Verifies an ES256, ES384 or ES512 signature over `data`, hashed with SHA-256, SHA-384 or
+    // SHA-512 respectively. Authenticators encode the signature as a DER
+    // `SEQUENCE { r INTEGER, s INTEGER }`. Anything that does not re-encode to the same bytes,
+    // e.g. trailing data or a non-minimal length, is an invalid signature rather than an error.
+    // See https://w3c.github.io/webauthn/#sctn-signature-attestation-types
+    pub fn verify_signature(&self, signature: &[u8], data: &[u8]) -> Result<bool, WebAuthnError> {
+        let digest = digest(self.alg)?;
+        let signature = match ecdsa::EcdsaSig::from_der(signature) {
+            Ok(sig) if sig.to_der().ok().as_deref() == Some(signature) => sig,
+            _ => return Ok(false),
+        };
+        let key = self.to_pkey()?.ec_key().map_err(WebAuthnError::OpenSSL)?;
+        let hash = hash::hash(digest, data).map_err(WebAuthnError::OpenSSL)?;
+        signature
+            .verify(&hash, &key)
+            .map_err(WebAuthnError::OpenSSL)
+    }
+}
+
+impl CoseOkpKey {
+    fn to_pkey(&self) -> Result<pkey::PKey<pkey::Public>, WebAuthnError> {
+        pkey::PKey::public_key_from_raw_bytes(&self.x, pkey::Id::ED25519)
+            .map_err(|_| WebAuthnError::InvalidPublicKey)
+    }
+
+    // Verifies an EdDSA signature over `data`. Ed25519 hashes the message itself, so no digest
+    // is applied beforehand.
+    pub fn verify_signature(&self, signature: &[u8], data: &[u8]) -> Result<bool, WebAuthnError> {
+        let key = self.to_pkey()?;
+        let mut verifier =
+            sign::Verifier::new_without_digest(&key).map_err(WebAuthnError::OpenSSL)?;
+        verifier
+            .verify_oneshot(signature, data)
+            .map_err(WebAuthnError::OpenSSL)
+    }
+}
+
+impl CoseRsaKey {
+    fn to_pkey(&self) -> Result<pkey::PKey<pkey::Public>, WebAuthnError> {
+        let n = bn::BigNum::from_slice(&self.n).map_err(WebAuthnError::OpenSSL)?;
+        let e = bn::BigNum::from_slice(&self.e).map_err(WebAuthnError::OpenSSL)?;
+        let rsa_key = rsa::Rsa::from_public_components(n, e).map_err(WebAuthnError::OpenSSL)?;
+        pkey::PKey::from_rsa(rsa_key).map_err(WebAuthnError::OpenSSL)
+    }
+
+    // Verifies an RS256, RS384 or RS512 (RSASSA-PKCS1-v1_5) or a PS256, PS384 or PS512
+    // (RSASSA-PSS) signature over `data`, hashed with SHA-256, SHA-384 or SHA-512 respectively.
+    pub fn verify_signature(&self, signature: &[u8], data: &[u8]) -> Result<bool, WebAuthnError> {
+        let key = self.to_pkey()?;
+        let mut verifier = verifier(self.alg, &key)?;
+        verifier.update(data).map_err(WebAuthnError::OpenSSL)?;
+        verifier.verify(signature).map_err(WebAuthnError::OpenSSL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{hex, TestKey};
+    use serde_cbor::{ObjectKey, Value};
+
+    // The P-256 key of the ECDSA test vectors in RFC 6979, appendix A.2.5, encoded the way
+    // authenticators encode ES256 keys, `{1: 2, 3: -7, -1: 1, -2: x, -3: y}`.
+    const P256_KEY: &str = concat!(
+        "a5010203262001215820",
+        "60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6",
+        "225820",
+        "7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299",
+    );
+
+    fn key_map(entries: &[(i64, Value)]) -> Vec<u8> {
+        let map = entries
+            .iter()
+            .map(|(label, value)| (ObjectKey::Integer(*label), value.clone()))
+            .collect::<BTreeMap<_, _>>();
+        serde_cbor::to_vec(&Value::Object(map)).unwrap()
+    }
+
+    #[test]
+    fn ec2_key() {
+        let bytes = hex(P256_KEY);
+        let key = CoseKey::try_from(bytes.as_slice()).unwrap();
+        match &key {
+            CoseKey::EC2(key) => {
+                assert_eq!(key.alg, ALG_ES256);
+                assert_eq!(key.curve, EC2_CURVE_P256);
+                assert_eq!(
+                    key.x,
+                    hex("60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6")
+                );
+                assert_eq!(
+                    key.y,
+                    hex("7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299")
+                );
+            }
+            _ => panic!("not an EC2 key: {:?}", key),
+        }
+        assert_eq!(key.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn ec2_point_not_on_curve() {
+        let mut bytes = hex(P256_KEY);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(
+            CoseKey::try_from(bytes.as_slice()),
+            Err(WebAuthnError::InvalidPublicKey)
+        ));
+    }
+
+    #[test]
+    fn ec2_key_missing_coordinate() {
+        let x = Value::Bytes(vec![1; 32]);
+        let bytes = key_map(&[
+            (KEY_TYPE, Value::I64(KEY_TYPE_EC2)),
+            (ALGORITHM, Value::I64(ALG_ES256)),
+            (EC2_CURVE, Value::I64(EC2_CURVE_P256)),
+            (EC2_X, x),
+        ]);
+        assert!(matches!(
+            CoseKey::try_from(bytes.as_slice()),
+            Err(WebAuthnError::InvalidPublicKey)
+        ));
+    }
+
+    #[test]
+    fn ec2_key_short_coordinate() {
+        let bytes = key_map(&[
+            (KEY_TYPE, Value::I64(KEY_TYPE_EC2)),
+            (ALGORITHM, Value::I64(ALG_ES256)),
+            (EC2_CURVE, Value::I64(EC2_CURVE_P256)),
+            (EC2_X, Value::Bytes(vec![1; 31])),
+            (EC2_Y, Value::Bytes(vec![1; 32])),
+        ]);
+        assert!(matches!(
+            CoseKey::try_from(bytes.as_slice()),
+            Err(WebAuthnError::InvalidPublicKey)
+        ));
+    }
+
+    #[test]
+    fn ec2_curves() {
+        let keys = vec![
+            TestKey::ec(nid::Nid::SECP384R1, ALG_ES384),
+            TestKey::ec(nid::Nid::SECP521R1, ALG_ES512),
+        ];
+        for key in &keys {
+            let cose_key = CoseKey::try_from(key.cose.as_slice()).unwrap();
+            assert_eq!(cose_key.alg(), key.alg);
+            let signature = key.sign(b"data");
+            assert!(cose_key.verify_signature(&signature, b"data").unwrap());
+            assert!(!cose_key.verify_signature(&signature, b"other").unwrap());
+        }
+    }
+
+    #[test]
+    fn ec2_coordinate_length_follows_curve() {
+        for &(alg, curve, len) in &[
+            (ALG_ES384, EC2_CURVE_P384, 32),
+            (ALG_ES384, EC2_CURVE_P384, 66),
+            (ALG_ES512, EC2_CURVE_P521, 48),
+        ] {
+            let bytes = key_map(&[
+                (KEY_TYPE, Value::I64(KEY_TYPE_EC2)),
+                (ALGORITHM, Value::I64(alg)),
+                (EC2_CURVE, Value::I64(curve)),
+                (EC2_X, Value::Bytes(vec![1; len])),
+                (EC2_Y, Value::Bytes(vec![1; len])),
+            ]);
+            assert!(matches!(
+                CoseKey::try_from(bytes.as_slice()),
+                Err(WebAuthnError::InvalidPublicKey)
+            ));
+        }
+    }
+
+    #[test]
+    fn ec2_curve_follows_alg() {
+        let key = TestKey::ec(nid::Nid::SECP384R1, ALG_ES256);
+        assert!(matches!(
+            CoseKey::try_from(key.cose.as_slice()),
+            Err(WebAuthnError::KeyAlgMismatch)
+        ));
+    }
+
+    #[test]
+    fn ed25519_key() {
+        let key = TestKey::ed25519();
+        let cose_key = CoseKey::try_from(key.cose.as_slice()).unwrap();
+        assert!(matches!(cose_key, CoseKey::OKP(_)));
+        let signature = key.sign(b"data");
+        assert!(cose_key.verify_signature(&signature, b"data").unwrap());
+        assert!(!cose_key.verify_signature(&signature, b"other").unwrap());
+    }
+
+    #[test]
+    fn okp_short_key() {
+        let bytes = key_map(&[
+            (KEY_TYPE, Value::I64(KEY_TYPE_OKP)),
+            (ALGORITHM, Value::I64(ALG_EDDSA)),
+            (OKP_CURVE, Value::I64(OKP_CURVE_ED25519)),
+            (OKP_X, Value::Bytes(vec![1; 31])),
+        ]);
+        assert!(matches!(
+            CoseKey::try_from(bytes.as_slice()),
+            Err(WebAuthnError::InvalidPublicKey)
+        ));
+    }
+
+    #[test]
+    fn okp_unsupported_curve() {
+        // Ed448
+        let bytes = key_map(&[
+            (KEY_TYPE, Value::I64(KEY_TYPE_OKP)),
+            (ALGORITHM, Value::I64(ALG_EDDSA)),
+            (OKP_CURVE, Value::I64(7)),
+            (OKP_X, Value::Bytes(vec![1; 57])),
+        ]);
+        assert!(matches!(
+            CoseKey::try_from(bytes.as_slice()),
+            Err(WebAuthnError::UnsupportedKeyType)
+        ));
+    }
+
+    #[test]
+    fn rsa_algorithms() {
+        for alg in &[
+            ALG_RS256, ALG_RS384, ALG_RS512, ALG_PS256, ALG_PS384, ALG_PS512,
+        ] {
+            let key = TestKey::rsa(*alg);
+            let cose_key = CoseKey::try_from(key.cose.as_slice()).unwrap();
+            let signature = key.sign(b"data");
+            assert!(cose_key.verify_signature(&signature, b"data").unwrap());
+            assert!(!cose_key.verify_signature(&signature, b"other").unwrap());
+        }
+    }
+
+    #[test]
+    fn rsa_padding_follows_alg() {
+        let key = TestKey::rsa(ALG_RS256);
+        let signature = key.sign(b"data");
+        let mut cose_key = CoseKey::try_from(key.cose.as_slice()).unwrap();
+        if let CoseKey::RSA(key) = &mut cose_key {
+            key.alg = ALG_PS256;
+        }
+        assert!(!cose_key.verify_signature(&signature, b"data").unwrap());
+    }
+
+    #[test]
+    fn rsa_unknown_alg() {
+        // RS1, RSASSA-PKCS1-v1_5 with SHA-1.
+        let key = TestKey::rsa(-65535);
+        assert!(matches!(
+            CoseKey::try_from(key.cose.as_slice()),
+            Err(WebAuthnError::KeyAlgMismatch)
+        ));
+    }
+
+    #[test]
+    fn es256_published_signature() {
+        // The SHA-256 signature of "sample" for the key in `P256_KEY`, from RFC 6979, appendix
+        // A.2.5, in the DER encoding authenticators use.
+        let signature = hex(concat!(
+            "3046022100efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716",
+            "022100f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+        ));
+        let key = CoseKey::try_from(hex(P256_KEY).as_slice()).unwrap();
+        assert!(key.verify_signature(&signature, b"sample").unwrap());
+        assert!(!key.verify_signature(&signature, b"samplf").unwrap());
+        let mut flipped = signature.clone();
+        flipped[10] ^= 1;
+        assert!(!key.verify_signature(&flipped, b"sample").unwrap());
+    }
+
+    #[test]
+    fn es256_der_signature() {
+        let key = TestKey::es256();
+        let cose_key = CoseKey::try_from(key.cose.as_slice()).unwrap();
+        // The authenticator data and client data hash of an assertion.
+        let mut data =
+            hex("a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce19470500000001");
+        data.extend_from_slice(&[0x5a; 32]);
+        let signature = key.sign(&data);
+        assert!(cose_key.verify_signature(&signature, &data).unwrap());
+
+        let mut flipped = data.clone();
+        flipped[32] ^= 1;
+        assert!(!cose_key.verify_signature(&signature, &flipped).unwrap());
+        let mut flipped = signature.clone();
+        let last = flipped.len() - 1;
+        flipped[last] ^= 1;
+        assert!(!cose_key.verify_signature(&flipped, &data).unwrap());
+        let mut trailing = signature;
+        trailing.push(0);
+        assert!(!cose_key.verify_signature(&trailing, &data).unwrap());
+    }
+
+    #[test]
+    fn spki() {
+        let keys = vec![
+            TestKey::es256(),
+            TestKey::ec(nid::Nid::SECP384R1, ALG_ES384),
+            TestKey::ec(nid::Nid::SECP521R1, ALG_ES512),
+            TestKey::rsa(ALG_RS256),
+            TestKey::ed25519(),
+        ];
+        for key in &keys {
+            let cose_key = CoseKey::try_from(key.cose.as_slice()).unwrap();
+            assert_eq!(
+                cose_key.to_spki_der().unwrap(),
+                key.key.public_key_to_der().unwrap()
+            );
+            assert_eq!(
+                cose_key.to_spki_pem().unwrap().as_bytes(),
+                &key.key.public_key_to_pem().unwrap()[..]
+            );
+        }
+
+        // An id-ecPublicKey on prime256v1 with the uncompressed point.
+        let key = CoseKey::try_from(hex(P256_KEY).as_slice()).unwrap();
+        let expected = hex(concat!(
+            "3059301306072a8648ce3d020106082a8648ce3d03010703420004",
+            "60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6",
+            "7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299",
+        ));
+        assert_eq!(key.to_spki_der().unwrap(), expected);
+        let pem = key.to_spki_pem().unwrap();
+        assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----\n"));
+        assert_eq!(
+            pkey::PKey::public_key_from_pem(pem.as_bytes())
+                .unwrap()
+                .public_key_to_der()
+                .unwrap(),
+            expected
+        );
+    }
+}
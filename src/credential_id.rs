@@ -0,0 +1,59 @@
+use crate::WebAuthnError;
+
+// The raw bytes identifying a credential, as chosen by the authenticator. Browsers and the
+// stored credentials carry it base64url encoded, but ids are always compared as bytes, so that
+// differently padded or encoded forms of the same id cannot be told apart or mixed up.
+// See https://w3c.github.io/webauthn/#credential-id
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CredentialId(Vec<u8>);
+
+impl CredentialId {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        CredentialId(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn as_base64url(&self) -> String {
+        crate::encode_base64url(&self.0)
+    }
+
+    pub fn from_base64url(data: &str) -> Result<Self, WebAuthnError> {
+        crate::decode_base64url(data).map(CredentialId)
+    }
+}
+
+impl From<Vec<u8>> for CredentialId {
+    fn from(bytes: Vec<u8>) -> Self {
+        CredentialId(bytes)
+    }
+}
+
+// Serialized as base64url in every format, which keeps stored credentials of earlier versions,
+// whose ids were base64url strings, readable.
+impl serde::Serialize for CredentialId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.as_base64url())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for CredentialId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        CredentialId::from_base64url(&data).map_err(serde::de::Error::custom)
+    }
+}
+
+impl std::fmt::Display for CredentialId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_base64url())
+    }
+}
+
+impl std::fmt::Debug for CredentialId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_base64url())
+    }
+}
@@ -0,0 +1,148 @@
+// A minimal DER reader, just enough to get at the extensions of attestation certificates that
+// OpenSSL does not expose.
+// See https://www.itu.int/rec/T-REC-X.690-201508-I/en
+
+use crate::WebAuthnError;
+
+pub const BOOLEAN: u32 = 0x01;
+pub const INTEGER: u32 = 0x02;
+pub const OCTET_STRING: u32 = 0x04;
+pub const OID: u32 = 0x06;
+pub const ENUMERATED: u32 = 0x0a;
+pub const SEQUENCE: u32 = 0x30;
+pub const SET: u32 = 0x31;
+
+// Tag of an explicitly tagged, context-specific field `[n]`. Tags are represented by their first
+// byte, with the tag number in the lower bits if it does not fit into that byte.
+pub fn context(n: u32) -> u32 {
+    if n < 0x1f {
+        0xa0 | n
+    } else {
+        0xbf << 24 | n
+    }
+}
+
+// Reads the element at the start of `v`, and returns its tag, its contents and the bytes that
+// follow it.
+pub fn read(v: &[u8]) -> Result<(u32, &[u8], &[u8]), WebAuthnError> {
+    let mut pos = 0;
+    let mut next = || {
+        let b = v.get(pos).cloned();
+        pos += 1;
+        b.ok_or(WebAuthnError::InvalidAttestationStatement)
+    };
+    let first = next()?;
+    let tag = if first & 0x1f == 0x1f {
+        // High tag numbers follow base 128 encoded.
+        let mut n = 0u32;
+        loop {
+            let b = next()?;
+            if n > 0x00ff_ffff {
+                return Err(WebAuthnError::InvalidAttestationStatement);
+            }
+            n = (n << 7) | u32::from(b & 0x7f);
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        u32::from(first) << 24 | n
+    } else {
+        u32::from(first)
+    };
+    let len = match next()? {
+        len if len < 0x80 => len as usize,
+        len @ 0x81..=0x84 => {
+            let mut n = 0usize;
+            for _ in 0..len & 0x7f {
+                n = (n << 8) | next()? as usize;
+            }
+            n
+        }
+        _ => return Err(WebAuthnError::InvalidAttestationStatement),
+    };
+    if v.len() - pos < len {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    Ok((tag, &v[pos..pos + len], &v[pos + len..]))
+}
+
+// Like `read`, but fails unless the element has the given tag.
+pub fn read_tagged(v: &[u8], tag: u32) -> Result<(&[u8], &[u8]), WebAuthnError> {
+    match read(v)? {
+        (t, contents, rest) if t == tag => Ok((contents, rest)),
+        _ => Err(WebAuthnError::InvalidAttestationStatement),
+    }
+}
+
+// Splits the contents of a SEQUENCE or SET into its elements, as (tag, contents) pairs.
+pub fn elements(mut v: &[u8]) -> Result<Vec<(u32, &[u8])>, WebAuthnError> {
+    let mut elements = Vec::new();
+    while !v.is_empty() {
+        let (tag, contents, rest) = read(v)?;
+        elements.push((tag, contents));
+        v = rest;
+    }
+    Ok(elements)
+}
+
+// Reads the contents of a non-negative INTEGER or ENUMERATED that fits into a u64.
+pub fn read_uint(contents: &[u8]) -> Result<u64, WebAuthnError> {
+    let (first, rest) = match contents.split_first() {
+        Some(split) => split,
+        None => return Err(WebAuthnError::InvalidAttestationStatement),
+    };
+    // A set high bit makes the value negative, and a leading zero only keeps it positive.
+    if first & 0x80 != 0 {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    let contents = if *first == 0 { rest } else { contents };
+    if contents.len() > 8 {
+        return Err(WebAuthnError::InvalidAttestationStatement);
+    }
+    Ok(contents.iter().fold(0, |n, &b| (n << 8) | u64::from(b)))
+}
+
+// Encodes the contents of an OBJECT IDENTIFIER, e.g. `[1, 2, 840, 113635, 100, 8, 2]`.
+pub fn encode_oid(arcs: &[u64]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let first = arcs[0] * 40 + arcs[1];
+    for &arc in std::iter::once(&first).chain(&arcs[2..]) {
+        let mut bytes = vec![(arc & 0x7f) as u8];
+        let mut arc = arc >> 7;
+        while arc > 0 {
+            bytes.push((arc & 0x7f) as u8 | 0x80);
+            arc >>= 7;
+        }
+        encoded.extend(bytes.iter().rev());
+    }
+    encoded
+}
+
+// Returns the value of the extension with the given OID of a DER encoded X.509 certificate.
+// See https://tools.ietf.org/html/rfc5280#section-4.1
+pub fn find_extension<'a>(cert: &'a [u8], oid: &[u64]) -> Result<Option<&'a [u8]>, WebAuthnError> {
+    let oid = encode_oid(oid);
+    let (certificate, _) = read_tagged(cert, SEQUENCE)?;
+    let (tbs_certificate, _) = read_tagged(certificate, SEQUENCE)?;
+    let extensions = match elements(tbs_certificate)?
+        .into_iter()
+        .find(|&(tag, _)| tag == context(3))
+    {
+        Some((_, extensions)) => read_tagged(extensions, SEQUENCE)?.0,
+        None => return Ok(None),
+    };
+    for (_, extension) in elements(extensions)? {
+        let (extn_id, rest) = read_tagged(extension, OID)?;
+        if extn_id != oid.as_slice() {
+            continue;
+        }
+        // The critical flag is optional.
+        let rest = match read(rest)? {
+            (BOOLEAN, _, rest) => rest,
+            _ => rest,
+        };
+        let (extn_value, _) = read_tagged(rest, OCTET_STRING)?;
+        return Ok(Some(extn_value));
+    }
+    Ok(None)
+}
@@ -0,0 +1,163 @@
+#[derive(Debug)]
+pub enum WebAuthnError {
+    Base64Decode(base64::DecodeError),
+    ClientDataParse(serde_json::Error),
+    AttestationParse(serde_cbor::error::Error),
+    CborParse(serde_cbor::error::Error),
+    InvalidClientDataType,
+    AuthDataTooShort,
+    CredentialNotFound,
+    InvalidPublicKey,
+    OpenSSL(openssl::error::ErrorStack),
+    ChallengeMismatch,
+    OriginMismatch,
+    // The expected and the received rpIdHash.
+    RpIdHashMismatch([u8; 32], Vec<u8>),
+    MissingAttestedCredentialData,
+    UserNotPresent,
+    UserNotVerified,
+    ChallengeExpired,
+    UnsupportedAttestationFormat(String),
+    InvalidAttestationStatement,
+    InvalidAttestationSignature,
+    UnsupportedAlgorithm(i64),
+    CounterRollback,
+    UnsupportedKeyType,
+    CredentialExcluded,
+    MissingRpId,
+    MalformedCredentialData,
+    CredentialIdMismatch,
+    UntrustedAttestation,
+    InvalidMetadata,
+    AuthenticatorNotAllowed,
+    MissingUserHandle,
+    UserHandleTooLong,
+    CredentialNotAllowed,
+    UnsupportedCredentialFormat(u8),
+    InvalidAaguid,
+    CredentialProtectionNotHonored,
+    KeyAlgMismatch,
+    BackupEligibilityChanged,
+    InvalidBackupFlags,
+    AssertionReplayed,
+    CredentialAlreadyRegistered,
+    InvalidAssertionSignature,
+    ChallengeTooShort(usize),
+    CrossOriginNotAllowed,
+    RngUnavailable,
+    AaguidNotAllowed,
+    MissingAttestation,
+    PublicKeyMismatch,
+    TrailingAuthData,
+}
+
+impl std::fmt::Display for WebAuthnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WebAuthnError::Base64Decode(e) => write!(f, "could not decode base64: {}", e),
+            WebAuthnError::ClientDataParse(e) => write!(f, "could not parse client data: {}", e),
+            WebAuthnError::AttestationParse(e) => {
+                write!(f, "could not parse attestation object: {}", e)
+            }
+            WebAuthnError::CborParse(e) => write!(f, "could not parse cbor: {}", e),
+            WebAuthnError::InvalidClientDataType => write!(f, "invalid client data type"),
+            WebAuthnError::AuthDataTooShort => write!(f, "authenticator data is too short"),
+            WebAuthnError::CredentialNotFound => write!(f, "credential not found"),
+            WebAuthnError::InvalidPublicKey => write!(f, "invalid credential public key"),
+            WebAuthnError::OpenSSL(e) => write!(f, "openssl error: {}", e),
+            WebAuthnError::ChallengeMismatch => write!(f, "challenge does not match"),
+            WebAuthnError::OriginMismatch => write!(f, "origin is not allowed"),
+            WebAuthnError::RpIdHashMismatch(expected, received) => write!(
+                f,
+                "relying party id hash does not match: expected {}, received {}",
+                hex(expected),
+                hex(received)
+            ),
+            WebAuthnError::MissingAttestedCredentialData => {
+                write!(f, "missing attested credential data")
+            }
+            WebAuthnError::UserNotPresent => write!(f, "user presence flag is not set"),
+            WebAuthnError::UserNotVerified => write!(f, "user verification flag is not set"),
+            WebAuthnError::ChallengeExpired => write!(f, "challenge has expired"),
+            WebAuthnError::UnsupportedAttestationFormat(fmt) => {
+                write!(f, "unsupported attestation format: {}", fmt)
+            }
+            WebAuthnError::InvalidAttestationStatement => {
+                write!(f, "invalid attestation statement")
+            }
+            WebAuthnError::InvalidAttestationSignature => {
+                write!(f, "attestation signature is invalid")
+            }
+            WebAuthnError::UnsupportedAlgorithm(alg) => write!(f, "unsupported algorithm: {}", alg),
+            WebAuthnError::CounterRollback => write!(f, "signature counter did not increase"),
+            WebAuthnError::UnsupportedKeyType => write!(f, "unsupported key type"),
+            WebAuthnError::CredentialExcluded => write!(f, "credential is already registered"),
+            WebAuthnError::MissingRpId => write!(f, "relying party id is not set"),
+            WebAuthnError::MalformedCredentialData => {
+                write!(f, "malformed attested credential data")
+            }
+            WebAuthnError::CredentialIdMismatch => write!(
+                f,
+                "credential id does not match the attested credential data"
+            ),
+            WebAuthnError::UntrustedAttestation => write!(f, "attestation is not trusted"),
+            WebAuthnError::InvalidMetadata => write!(f, "invalid authenticator metadata"),
+            WebAuthnError::AuthenticatorNotAllowed => {
+                write!(f, "authenticator is not allowed by the metadata policy")
+            }
+            WebAuthnError::MissingUserHandle => write!(f, "assertion is missing the user handle"),
+            WebAuthnError::UserHandleTooLong => write!(f, "user handle is longer than 64 bytes"),
+            WebAuthnError::CredentialNotAllowed => {
+                write!(f, "credential was not in the allowed credentials")
+            }
+            WebAuthnError::UnsupportedCredentialFormat(version) => {
+                write!(f, "unsupported credential format version {}", version)
+            }
+            WebAuthnError::InvalidAaguid => write!(f, "invalid aaguid"),
+            WebAuthnError::CredentialProtectionNotHonored => write!(
+                f,
+                "authenticator did not apply the requested credential protection"
+            ),
+            WebAuthnError::KeyAlgMismatch => write!(f, "key algorithm does not match the key type"),
+            WebAuthnError::BackupEligibilityChanged => {
+                write!(f, "backup eligibility of the credential changed")
+            }
+            WebAuthnError::InvalidBackupFlags => {
+                write!(f, "backup state flag is set without backup eligibility")
+            }
+            WebAuthnError::AssertionReplayed => write!(f, "assertion was already used"),
+            WebAuthnError::CredentialAlreadyRegistered => {
+                write!(f, "credential is already registered")
+            }
+            WebAuthnError::InvalidAssertionSignature => write!(f, "invalid assertion signature"),
+            WebAuthnError::ChallengeTooShort(size) => {
+                write!(f, "challenges must be at least 16 bytes, not {}", size)
+            }
+            WebAuthnError::CrossOriginNotAllowed => {
+                write!(f, "cross-origin ceremonies are not allowed")
+            }
+            WebAuthnError::RngUnavailable => {
+                write!(f, "no random bytes are available for challenges")
+            }
+            WebAuthnError::AaguidNotAllowed => {
+                write!(f, "authenticator model is not on the enterprise allowlist")
+            }
+            WebAuthnError::MissingAttestation => {
+                write!(f, "authenticator did not identify its model")
+            }
+            WebAuthnError::PublicKeyMismatch => write!(
+                f,
+                "attestation statement is for a different key than the credential"
+            ),
+            WebAuthnError::TrailingAuthData => {
+                write!(f, "authenticator data has bytes after its contents")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WebAuthnError {}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
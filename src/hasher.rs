@@ -0,0 +1,21 @@
+use sha2::Digest;
+
+// The SHA-256 implementation used for the relying party id hash and the client data hash, e.g.
+// to use a FIPS validated module instead of the `sha2` crate. Signatures and certificates are
+// hashed by OpenSSL as part of their verification.
+pub trait Sha256Hasher: std::fmt::Debug + Send + Sync {
+    fn sha256(&self, data: &[u8]) -> [u8; 32];
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha2Hasher;
+
+impl Sha256Hasher for Sha2Hasher {
+    fn sha256(&self, data: &[u8]) -> [u8; 32] {
+        let mut hasher = sha2::Sha256::new();
+        hasher.input(data);
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&hasher.result());
+        hash
+    }
+}
@@ -1,106 +1,1996 @@
 #[macro_use]
 extern crate serde_derive;
 extern crate base64;
+#[cfg(feature = "logging")]
 #[macro_use]
 extern crate log;
 extern crate byteorder;
+extern crate openssl;
 extern crate sha2;
 
-use sha2::Digest;
+// Without the `logging` feature, log statements compile to nothing. The arguments are still
+// type-checked, so values only used for logging do not count as unused.
+#[cfg(not(feature = "logging"))]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+#[cfg(not(feature = "logging"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+#[cfg(not(feature = "logging"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
 
+use std::convert::TryFrom;
+
+pub mod attestation;
+mod builder;
 mod challenge;
+mod clock;
+pub mod cose;
+mod credential_id;
+mod der;
+mod error;
+mod hasher;
+pub mod metadata;
+pub mod options;
+mod relying_parties;
+mod replay;
 pub mod requests;
+mod store;
+#[cfg(test)]
+mod test_util;
+mod tpm;
+mod trust;
+
+pub use crate::builder::WebAuthnBuilder;
+pub use crate::challenge::{Challenge, ChallengeRng, DefaultRng};
+pub use crate::clock::{Clock, SystemClock};
+pub use crate::credential_id::CredentialId;
+pub use crate::error::WebAuthnError;
+pub use crate::hasher::{Sha256Hasher, Sha2Hasher};
+pub use crate::relying_parties::RelyingParties;
+pub use crate::replay::{InMemoryAssertionCache, SeenAssertionCache};
+#[cfg(feature = "tokio")]
+pub use crate::store::AsyncCredentialStore;
+pub use crate::store::CredentialStore;
+pub use crate::trust::TrustAnchorStore;
 
 const CHALLENGE_SIZE_BYTES: usize = 32;
+// See https://w3c.github.io/webauthn/#sctn-cryptographic-challenges
+const MIN_CHALLENGE_SIZE_BYTES: usize = 16;
+const DEFAULT_TIMEOUT_MS: u32 = 60000;
+const CHALLENGE_TIMEOUT_SECS: u64 = 5 * 60;
+// Options are handed out before anyone is authenticated, so without a limit clients could make
+// the outstanding challenges grow forever.
+const MAX_CHALLENGES: usize = 10_000;
+// See https://w3c.github.io/webauthn/#dom-publickeycredentialuserentity-id
+const MAX_USER_HANDLE_LENGTH: usize = 64;
 
 type UserId = String;
 
-#[derive(Debug, Clone)]
+// WebAuthn transmits binary fields as base64url without padding, but padded input is tolerated as
+// well.
+fn decode_base64url(data: &str) -> Result<Vec<u8>, WebAuthnError> {
+    base64::decode_config(data.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .map_err(WebAuthnError::Base64Decode)
+}
+
+fn encode_base64url(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+// Compares two byte strings in time that only depends on their lengths, so that comparing e.g.
+// challenges does not reveal how many leading bytes matched.
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && openssl::memcmp::eq(a, b)
+}
+
+// Brings an origin into a canonical form so that equivalent serializations compare equal: scheme
+// and host are case-insensitive, a trailing slash is not significant, and the default port for
+// the scheme may be omitted. Any other port is kept, since `http://localhost:8000` and
+// `http://localhost:8080` are different origins.
+fn normalize_origin(origin: &str) -> String {
+    let origin = origin.trim_end_matches('/').to_lowercase();
+    if origin.starts_with("https://") && origin.ends_with(":443") {
+        origin.trim_end_matches(":443").to_string()
+    } else if origin.starts_with("http://") && origin.ends_with(":80") {
+        origin.trim_end_matches(":80").to_string()
+    } else {
+        origin
+    }
+}
+
+// The version of the format written by `Credential::to_bytes`.
+const CREDENTIAL_FORMAT_VERSION: u8 = 1;
+
+// A registered credential, as kept by a `CredentialStore`.
+//
+// Besides the serde implementations, e.g. for JSON, `to_bytes` gives a compact binary form for
+// storing credentials in a database. Fields added in later versions have defaults, so that stored
+// credentials keep loading after an upgrade.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Credential {
-    pub id: String,
+    pub id: CredentialId,
+    pub public_key: cose::CoseKey,
+    pub counter: u32,
+    // The model of the authenticator the credential was created on.
+    pub aaguid: requests::Aaguid,
+    // The user id given to `creation_options`, which authenticators return as the user handle of
+    // discoverable credentials.
+    #[serde(with = "serde_bytes")]
+    pub user_handle: Vec<u8>,
+    // How the authenticator holding the credential can be reached, as reported by the browser
+    // at registration.
+    #[serde(default)]
+    pub transports: Vec<options::AuthenticatorTransport>,
+    pub created_at: std::time::SystemTime,
+    // When the credential was last used to log in, if ever.
+    #[serde(default)]
+    pub last_used: Option<std::time::SystemTime>,
+    // A label chosen by the user, e.g. "YubiKey at work", to tell their credentials apart.
+    #[serde(default)]
+    pub name: Option<String>,
+    // Whether the credential may be synced to other devices, which never changes, and whether it
+    // was backed up as of the last login. Credentials that are not backed up are lost with
+    // their authenticator.
+    #[serde(default)]
+    pub backup_eligible: bool,
+    #[serde(default)]
+    pub backup_state: bool,
+    // Whether the credential is discoverable, i.e. usable for logins without a username, if the
+    // client reported it at registration.
+    #[serde(default)]
+    pub resident_key: Option<bool>,
+}
+
+impl Credential {
+    // Encodes the credential as a format version byte followed by CBOR.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, WebAuthnError> {
+        let mut bytes = vec![CREDENTIAL_FORMAT_VERSION];
+        bytes.extend(serde_cbor::to_vec(self).map_err(WebAuthnError::CborParse)?);
+        Ok(bytes)
+    }
+
+    // Decodes a credential written by `to_bytes` of this or an earlier version of the crate.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WebAuthnError> {
+        match bytes.split_first() {
+            Some((&CREDENTIAL_FORMAT_VERSION, cbor)) => {
+                serde_cbor::from_slice(cbor).map_err(WebAuthnError::CborParse)
+            }
+            Some((&version, _)) => Err(WebAuthnError::UnsupportedCredentialFormat(version)),
+            None => Err(WebAuthnError::MalformedCredentialData),
+        }
+    }
+
+    // Whether the credential has not been used to log in for longer than `max_idle`. Credentials
+    // that were never used count from their registration.
+    fn is_idle(&self, now: std::time::SystemTime, max_idle: std::time::Duration) -> bool {
+        let last_used = self.last_used.unwrap_or(self.created_at);
+        matches!(now.duration_since(last_used), Ok(idle) if idle > max_idle)
+    }
+}
+
+// What an account management page needs to show about a credential.
+#[derive(Debug, Clone)]
+pub struct CredentialInfo {
+    pub id: CredentialId,
+    pub aaguid: requests::Aaguid,
+    pub created_at: std::time::SystemTime,
+    pub last_used: Option<std::time::SystemTime>,
+    // The signature counter as of the last login, zero if the authenticator has none.
+    pub counter: u32,
+    pub name: Option<String>,
+    pub backup_eligible: bool,
+    pub backup_state: bool,
+    pub resident_key: Option<bool>,
+}
+
+impl From<&Credential> for CredentialInfo {
+    fn from(credential: &Credential) -> Self {
+        CredentialInfo {
+            id: credential.id.clone(),
+            aaguid: credential.aaguid,
+            created_at: credential.created_at,
+            last_used: credential.last_used,
+            counter: credential.counter,
+            name: credential.name.clone(),
+            backup_eligible: credential.backup_eligible,
+            backup_state: credential.backup_state,
+            resident_key: credential.resident_key,
+        }
+    }
+}
+
+// What was learned about a newly registered credential.
+#[derive(Debug, Clone)]
+pub struct RegistrationResult {
+    pub credential: Credential,
+    // How far the authenticator can be trusted; see `attestation::verify_attestation`.
+    pub attestation_type: attestation::AttestationType,
+    // What the attestation statement format tells beyond the attestation type, if anything.
+    pub attestation_details: Option<attestation::AttestationDetails>,
+    // The identity of the individual authenticator, if it returned an enterprise attestation.
+    pub enterprise_attestation: Option<attestation::EnterpriseAttestation>,
+    // Identifies the authenticator model, e.g. for lookups in the FIDO metadata service. All
+    // zeros if the authenticator does not disclose it.
+    pub aaguid: requests::Aaguid,
+    // Whether the credential is discoverable. The authenticator data does not tell, so this is
+    // `None` unless the client reported it through the `credProps` extension.
+    pub resident_key: Option<bool>,
+    // The protection level the authenticator applied through the `credProtect` extension, if it
+    // reported one.
+    pub credential_protection: Option<options::CredentialProtectionPolicy>,
+    // The outputs of the extensions requested for the registration, as reported by the client.
+    pub extensions: requests::AuthenticationExtensionsClientOutputs,
+}
+
+// Who logged in, as verified by `WebAuthn::verify_assertion`.
+#[derive(Debug, Clone)]
+pub struct AssertionResult {
+    pub credential_id: CredentialId,
+    // The user the credential is stored for.
+    pub user_id: UserId,
+    // The user handle the credential was registered with.
+    pub user_handle: Vec<u8>,
+    // The signature counter reported by the authenticator, which is now stored with the
+    // credential.
+    pub counter: u32,
+    pub user_verified: bool,
+    pub backup_state: bool,
+    // The outputs of the extensions requested for the login, as reported by the client.
+    pub extensions: requests::AuthenticationExtensionsClientOutputs,
+}
+
+// Whether the authenticator has to verify the user (e.g. by PIN or biometric) in addition to
+// testing for their presence.
+// See https://w3c.github.io/webauthn/#enumdef-userverificationrequirement
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UserVerificationPolicy {
+    Required,
+    Preferred,
+    Discouraged,
 }
 
 #[derive(Debug)]
-pub struct WebAuthn {
-    relying_party: String,
-    challenges: std::collections::HashMap<UserId, challenge::Challenge>,
-    credentials: std::collections::HashMap<UserId, Vec<Credential>>,
+pub struct WebAuthn<S = std::collections::HashMap<UserId, Vec<Credential>>> {
+    // The domain credentials are scoped to, e.g. `example.com`.
+    rp_id: String,
+    // The human-readable name shown to users, e.g. `Example Corp`.
+    rp_name: String,
+    origins: Vec<String>,
+    allow_cross_origin: bool,
+    user_verification_policy: UserVerificationPolicy,
+    supported_algorithms: Vec<i64>,
+    timeout_ms: u32,
+    attestation: options::AttestationConveyancePreference,
+    authenticator_selection: Option<options::AuthenticatorSelectionCriteria>,
+    trust_anchors: TrustAnchorStore,
+    metadata: Option<(metadata::MetadataService, metadata::MetadataPolicy)>,
+    enterprise_aaguid_allowlist: Option<Vec<requests::Aaguid>>,
+    require_aaguid: bool,
+    credential_protection: Option<(options::CredentialProtectionPolicy, bool)>,
+    app_id: Option<String>,
+    large_blob_support: Option<options::LargeBlobSupport>,
+    challenges: Vec<challenge::IssuedChallenge>,
+    challenge_timeout: std::time::Duration,
+    max_challenges: usize,
+    credential_max_idle: Option<std::time::Duration>,
+    challenge_size: usize,
+    rng: Box<dyn ChallengeRng>,
+    clock: Box<dyn Clock>,
+    hasher: Box<dyn Sha256Hasher>,
+    seen_assertions: Option<Box<dyn SeenAssertionCache>>,
+    credentials: S,
 }
 
 impl WebAuthn {
-    pub fn new(relying_party: String) -> Self {
+    pub fn new(rp_id: String) -> Self {
+        WebAuthn::with_store(rp_id, std::collections::HashMap::new())
+    }
+}
+
+impl<S> WebAuthn<S> {
+    pub fn with_store(rp_id: String, store: S) -> Self {
         WebAuthn {
-            relying_party: relying_party,
-            challenges: std::collections::HashMap::new(),
-            credentials: std::collections::HashMap::new(),
+            origins: vec![format!("https://{}", rp_id)],
+            rp_name: rp_id.clone(),
+            allow_cross_origin: false,
+            rp_id,
+            user_verification_policy: UserVerificationPolicy::Preferred,
+            supported_algorithms: vec![cose::ALG_ES256, cose::ALG_RS256, cose::ALG_EDDSA],
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            attestation: options::AttestationConveyancePreference::None,
+            authenticator_selection: None,
+            trust_anchors: TrustAnchorStore::default(),
+            metadata: None,
+            enterprise_aaguid_allowlist: None,
+            require_aaguid: false,
+            credential_protection: None,
+            app_id: None,
+            large_blob_support: None,
+            challenges: Vec::new(),
+            challenge_timeout: std::time::Duration::from_secs(CHALLENGE_TIMEOUT_SECS),
+            max_challenges: MAX_CHALLENGES,
+            credential_max_idle: None,
+            challenge_size: CHALLENGE_SIZE_BYTES,
+            rng: Box::new(DefaultRng),
+            clock: Box::new(SystemClock),
+            hasher: Box::new(Sha2Hasher),
+            seen_assertions: None,
+            credentials: store,
         }
     }
 
-    pub fn relying_party(&self) -> String {
-        self.relying_party.clone()
+    pub fn rp_id(&self) -> String {
+        self.rp_id.clone()
     }
 
-    // See https://developer.mozilla.org/en-US/docs/Web/API/Web_Authentication_API
-    // https://w3c.github.io/webauthn/#registering-a-new-credential
-    pub fn generate_challenge(&mut self, user_id: UserId) -> challenge::Challenge {
-        let challenge = challenge::Challenge::new(CHALLENGE_SIZE_BYTES);
-        self.challenges.insert(user_id, challenge.clone());
-        challenge
+    // The rpIdHash authenticators are expected to report, for comparing with the authenticator
+    // data when diagnosing a misconfigured relying party id.
+    pub fn expected_rp_id_hash(&self) -> [u8; 32] {
+        self.hasher.sha256(self.rp_id.as_bytes())
     }
 
-    pub fn get_credentials(&self, user_id: UserId) -> Vec<Credential> {
-        self.credentials.get(&user_id).unwrap_or(&vec![]).to_vec()
+    pub fn rp_name(&self) -> String {
+        self.rp_name.clone()
+    }
+
+    // Sets the relying party name shown to users during registration. Defaults to the id.
+    pub fn set_rp_name(&mut self, rp_name: String) {
+        self.rp_name = rp_name;
+    }
+
+    pub fn set_user_verification_policy(&mut self, policy: UserVerificationPolicy) {
+        self.user_verification_policy = policy;
+    }
+
+    // Sets the COSE algorithms that credentials may use, in order of preference. Defaults to
+    // ES256, RS256 and EdDSA; ES384, ES512, RS384, RS512 and PS256, PS384 and PS512 are also
+    // supported.
+    pub fn set_supported_algorithms(&mut self, algorithms: Vec<i64>) {
+        self.supported_algorithms = algorithms;
+    }
+
+    // Sets the requirements on authenticators used to register new credentials, e.g. to only
+    // allow platform authenticators or to require a resident key.
+    pub fn set_authenticator_selection(
+        &mut self,
+        authenticator_selection: Option<options::AuthenticatorSelectionCriteria>,
+    ) {
+        self.authenticator_selection = authenticator_selection;
+    }
+
+    // Sets how much the authenticator should tell about itself when registering. Defaults to
+    // `None`. Browsers and authenticators may still return less than requested, e.g. a `none`
+    // attestation for `Direct`, which is then reported as `AttestationType::None`.
+    pub fn set_attestation(&mut self, attestation: options::AttestationConveyancePreference) {
+        self.attestation = attestation;
+    }
+
+    // Sets the roots that attestation certificate chains are validated against. By default any
+    // attestation is accepted; see `TrustAnchorStore`.
+    pub fn set_trust_anchors(&mut self, trust_anchors: TrustAnchorStore) {
+        self.trust_anchors = trust_anchors;
+    }
+
+    pub fn trust_anchors_mut(&mut self) -> &mut TrustAnchorStore {
+        &mut self.trust_anchors
+    }
+
+    // Sets the metadata that registering authenticators are checked against with `policy`.
+    // Attestation chains then also have to lead up to the roots that the metadata lists for the
+    // attested model. Reload it by calling this again once `next_update` has passed.
+    pub fn set_metadata(
+        &mut self,
+        metadata: metadata::MetadataService,
+        policy: metadata::MetadataPolicy,
+    ) {
+        self.metadata = Some((metadata, policy));
+    }
+
+    // Only lets authenticator models in `allowlist` register, e.g. the security keys an
+    // enterprise hands out to its staff. Other models are rejected with `AaguidNotAllowed`. The
+    // AAGUID is only as trustworthy as the attestation, so combine this with
+    // `AttestationConveyancePreference::Enterprise` or `Direct` and trust anchors that require a
+    // trusted chain.
+    pub fn set_enterprise_aaguid_allowlist(&mut self, allowlist: Option<Vec<requests::Aaguid>>) {
+        self.enterprise_aaguid_allowlist = allowlist;
+    }
+
+    // Rejects registrations with the all-zero AAGUID, which authenticators report when they do
+    // not identify their model, with `MissingAttestation`. Meant for deployments that request
+    // `Direct` or `Enterprise` attestation to tell devices apart. Off by default.
+    pub fn set_require_aaguid(&mut self, require_aaguid: bool) {
+        self.require_aaguid = require_aaguid;
+    }
+
+    // Asks authenticators to restrict when new credentials may be used, e.g. to always require
+    // user verification for discoverable credentials. With `enforce`, registrations are rejected
+    // unless the authenticator applied at least the requested level.
+    pub fn set_credential_protection(
+        &mut self,
+        policy: Option<options::CredentialProtectionPolicy>,
+        enforce: bool,
+    ) {
+        self.credential_protection = policy.map(|policy| (policy, enforce));
+    }
+
+    // Sets the U2F app id, e.g. `https://example.com/app-id.json`, that credentials registered
+    // through the U2F JavaScript API are scoped to. It is sent along with the request options,
+    // so that those credentials keep working for logins.
+    // See https://w3c.github.io/webauthn/#sctn-appid-extension
+    pub fn set_app_id(&mut self, app_id: Option<String>) {
+        self.app_id = app_id;
+    }
+
+    // Asks for new credentials that can store a blob on their authenticator, which registrations
+    // report in `extensions.large_blob.supported`. The blob is read or written by setting
+    // `extensions.large_blob` of the request options, e.g. to
+    // `AuthenticationExtensionsLargeBlobInputs::read()`.
+    // See https://w3c.github.io/webauthn/#sctn-large-blob-extension
+    pub fn set_large_blob_support(&mut self, support: Option<options::LargeBlobSupport>) {
+        self.large_blob_support = support;
+    }
+
+    pub fn metadata(&self) -> Option<&metadata::MetadataService> {
+        self.metadata.as_ref().map(|(metadata, _)| metadata)
     }
 
-    pub fn register(&mut self, req: &requests::RegisterRequest) -> bool {
-        info!("req: {:?}", req);
-        let decoded_client_data_json_vec =
-            base64::decode(&req.response.client_data_json).expect("could not convert client data");
+    // Replaces the source of challenges, e.g. with one backed by `crypto.getRandomValues()` when
+    // running in a browser.
+    pub fn set_challenge_rng<R: ChallengeRng + 'static>(&mut self, rng: R) {
+        self.rng = Box::new(rng);
+    }
+
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.clock = Box::new(clock);
+    }
+
+    pub fn set_hasher<H: Sha256Hasher + 'static>(&mut self, hasher: H) {
+        self.hasher = Box::new(hasher);
+    }
+
+    // Rejects assertions that were already accepted once, as recorded in `cache`. Off by default.
+    pub fn set_seen_assertion_cache<C: SeenAssertionCache + 'static>(&mut self, cache: C) {
+        self.seen_assertions = Some(Box::new(cache));
+    }
+
+    // Sets how long the browser waits for the user to complete a ceremony, unless overridden in
+    // the options for a single ceremony. Defaults to one minute.
+    pub fn set_timeout_ms(&mut self, timeout_ms: u32) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    // Sets how long an issued challenge stays valid. Defaults to five minutes.
+    pub fn set_challenge_timeout(&mut self, timeout: std::time::Duration) {
+        self.challenge_timeout = timeout;
+    }
+
+    // Sets how many challenges may be outstanding at once. Once there are that many, issuing a
+    // new one drops the oldest. Defaults to 10000.
+    pub fn set_max_challenges(&mut self, max_challenges: usize) {
+        self.max_challenges = max_challenges.max(1);
+    }
+
+    // Sets how long a credential may go unused before `expire_idle_credentials` removes it.
+    // Credentials never expire by default.
+    pub fn set_credential_max_idle(&mut self, max_idle: Option<std::time::Duration>) {
+        self.credential_max_idle = max_idle;
+    }
+
+    // Sets the number of random bytes in challenges. Defaults to 32; the specification requires
+    // at least 16.
+    pub fn set_challenge_size(&mut self, size_bytes: usize) -> Result<(), WebAuthnError> {
+        if size_bytes < MIN_CHALLENGE_SIZE_BYTES {
+            return Err(WebAuthnError::ChallengeTooShort(size_bytes));
+        }
+        self.challenge_size = size_bytes;
+        Ok(())
+    }
+
+    // Sets the origins that ceremonies are accepted from, e.g. `https://example.com` or
+    // `http://localhost:8000`. Defaults to `https://<rp id>`.
+    pub fn set_origins(&mut self, origins: Vec<String>) {
+        self.origins = origins;
+    }
+
+    // Accepts ceremonies from pages of our origins that are embedded in other sites, e.g. a
+    // payment page in an iframe. Rejected by default.
+    pub fn set_allow_cross_origin(&mut self, allow_cross_origin: bool) {
+        self.allow_cross_origin = allow_cross_origin;
+    }
+
+    // The authenticator data carries SHA-256 of the relying party id it was produced for, which
+    // must be ours. Credentials scoped to a U2F app id hash that instead; `app_id` is the one the
+    // client reports to have fallen back to.
+    fn check_rp_id_hash(
+        &self,
+        rpid_hash: &[u8],
+        app_id: Option<&str>,
+    ) -> Result<(), WebAuthnError> {
+        let id = app_id.unwrap_or(&self.rp_id);
+        let expected = self.hasher.sha256(id.as_bytes());
+        if expected == rpid_hash {
+            Ok(())
+        } else {
+            Err(WebAuthnError::RpIdHashMismatch(
+                expected,
+                rpid_hash.to_vec(),
+            ))
+        }
+    }
+
+    fn check_user_flags(
+        &self,
+        decoded_auth_data: &requests::DecodedAuthData,
+        user_verification: UserVerificationPolicy,
+    ) -> Result<(), WebAuthnError> {
+        if !decoded_auth_data.user_present {
+            return Err(WebAuthnError::UserNotPresent);
+        }
+        if user_verification == UserVerificationPolicy::Required && !decoded_auth_data.user_verified
+        {
+            return Err(WebAuthnError::UserNotVerified);
+        }
+        Ok(())
+    }
+
+    fn check_origin(&self, origin: &str) -> Result<(), WebAuthnError> {
+        let origin = normalize_origin(origin);
+        if self.origins.iter().any(|o| normalize_origin(o) == origin) {
+            Ok(())
+        } else {
+            Err(WebAuthnError::OriginMismatch)
+        }
+    }
+
+    // Ceremonies run in an iframe embedded by another origin, which may be a site the user does
+    // not expect to sign in to, are only accepted if allowed.
+    // See https://w3c.github.io/webauthn/#dom-collectedclientdata-crossorigin
+    fn check_cross_origin(&self, client_data: &requests::ClientData) -> Result<(), WebAuthnError> {
+        if client_data.cross_origin == Some(true) && !self.allow_cross_origin {
+            debug!("cross-origin ceremony from {:?}", client_data.top_origin);
+            return Err(WebAuthnError::CrossOriginNotAllowed);
+        }
+        Ok(())
+    }
+
+    // Issues a challenge for a ceremony of `user_id`, whose registered credentials are
+    // `existing`. `user_handle` is only known when registering, and `app_id` only when logging in
+    // with the appid extension.
+    fn issue_challenge(
+        &mut self,
+        ceremony: challenge::Ceremony,
+        user_id: Option<UserId>,
+        user_handle: Option<Vec<u8>>,
+        app_id: Option<String>,
+        existing: &[Credential],
+    ) -> Result<Challenge, WebAuthnError> {
+        let challenge = Challenge::generate(self.challenge_size, self.rng.as_mut())?;
+        // Earlier challenges of the user stay valid, e.g. for a ceremony in another tab, until
+        // they expire or too many newer ones were issued.
+        self.prune_expired_challenges();
+        if self.challenges.len() >= self.max_challenges {
+            let excess = self.challenges.len() + 1 - self.max_challenges;
+            self.challenges.drain(..excess);
+        }
+        self.challenges.push(challenge::IssuedChallenge {
+            challenge: challenge.clone(),
+            ceremony,
+            issued_at: self.clock.now(),
+            user_id,
+            user_handle,
+            app_id,
+            user_verification: self.user_verification_policy,
+            user_credentials: existing.iter().map(|c| c.id.clone()).collect(),
+        });
+        Ok(challenge)
+    }
+
+    // Issues a challenge for `generate_challenge`, where the user id doubles as the user handle of
+    // the new credential and so is subject to the same limit.
+    fn registration_challenge(
+        &mut self,
+        user_id: UserId,
+        existing: &[Credential],
+    ) -> Result<Challenge, WebAuthnError> {
+        if user_id.len() > MAX_USER_HANDLE_LENGTH {
+            return Err(WebAuthnError::UserHandleTooLong);
+        }
+        self.issue_challenge(
+            challenge::Ceremony::Registration,
+            Some(user_id),
+            None,
+            None,
+            existing,
+        )
+    }
+
+    // The selection criteria are omitted unless configured, or unless the user verification
+    // policy differs from the default the browser assumes.
+    fn authenticator_selection(&self) -> Option<options::AuthenticatorSelectionCriteria> {
+        let mut selection = match &self.authenticator_selection {
+            Some(selection) => selection.clone(),
+            None if self.user_verification_policy == UserVerificationPolicy::Preferred => {
+                return None
+            }
+            None => options::AuthenticatorSelectionCriteria::default(),
+        };
+        if selection.user_verification.is_none() {
+            selection.user_verification = Some(self.user_verification_policy);
+        }
+        Some(selection)
+    }
+
+    fn build_creation_options(
+        &mut self,
+        user_id: Vec<u8>,
+        username: String,
+        display_name: String,
+        timeout_ms: Option<u32>,
+        existing: Vec<Credential>,
+    ) -> Result<options::PublicKeyCredentialCreationOptions, WebAuthnError> {
+        if user_id.len() > MAX_USER_HANDLE_LENGTH {
+            return Err(WebAuthnError::UserHandleTooLong);
+        }
+        let challenge = self.issue_challenge(
+            challenge::Ceremony::Registration,
+            Some(username.clone()),
+            Some(user_id.clone()),
+            None,
+            &existing,
+        )?;
+        Ok(options::PublicKeyCredentialCreationOptions {
+            rp: options::PublicKeyCredentialRpEntity {
+                id: self.rp_id.clone(),
+                name: self.rp_name.clone(),
+            },
+            user: options::PublicKeyCredentialUserEntity {
+                id: encode_base64url(&user_id),
+                name: username,
+                display_name,
+            },
+            challenge,
+            pub_key_cred_params: self
+                .supported_algorithms
+                .iter()
+                .map(|&alg| options::PublicKeyCredentialParameters {
+                    type_: "public-key".to_string(),
+                    alg,
+                })
+                .collect(),
+            timeout: timeout_ms.unwrap_or(self.timeout_ms),
+            attestation: self.attestation,
+            authenticator_selection: self.authenticator_selection(),
+            // Credentials the user already has are excluded, so that the same authenticator is
+            // not registered twice.
+            exclude_credentials: credential_descriptors(existing),
+            extensions: self.creation_extensions(),
+        })
+    }
+
+    fn creation_extensions(&self) -> options::AuthenticationExtensionsClientInputs {
+        let mut extensions = options::AuthenticationExtensionsClientInputs {
+            cred_props: Some(true),
+            ..Default::default()
+        };
+        if let Some((policy, enforce)) = self.credential_protection {
+            extensions.credential_protection_policy = Some(policy);
+            if enforce {
+                extensions.enforce_credential_protection_policy = Some(true);
+            }
+        }
+        if let Some(support) = self.large_blob_support {
+            extensions.large_blob = Some(options::AuthenticationExtensionsLargeBlobInputs {
+                support: Some(support),
+                ..Default::default()
+            });
+        }
+        extensions
+    }
+
+    fn build_request_options(
+        &mut self,
+        username: String,
+        timeout_ms: Option<u32>,
+        existing: Vec<Credential>,
+    ) -> Result<options::PublicKeyCredentialRequestOptions, WebAuthnError> {
+        let app_id = self.app_id.clone();
+        let challenge = self.issue_challenge(
+            challenge::Ceremony::Authentication,
+            Some(username),
+            None,
+            app_id.clone(),
+            &existing,
+        )?;
+        Ok(options::PublicKeyCredentialRequestOptions {
+            challenge,
+            timeout: timeout_ms.unwrap_or(self.timeout_ms),
+            rp_id: self.rp_id.clone(),
+            allow_credentials: credential_descriptors(existing),
+            user_verification: self.user_verification_policy,
+            extensions: options::AuthenticationExtensionsClientInputs {
+                appid: app_id,
+                ..Default::default()
+            },
+        })
+    }
+
+    // Issues a challenge for logging in with a discoverable credential, and returns the options to
+    // pass to `navigator.credentials.get()`. The allow list is empty, so the authenticator offers
+    // whichever credentials it holds for the relying party, and the user is only known once the
+    // assertion returns with its user handle. Complete the ceremony with `verify_discoverable`.
+    // See https://w3c.github.io/webauthn/#client-side-discoverable-credential
+    pub fn request_options_discoverable(
+        &mut self,
+        timeout_ms: Option<u32>,
+    ) -> Result<options::PublicKeyCredentialRequestOptions, WebAuthnError> {
+        let challenge =
+            self.issue_challenge(challenge::Ceremony::Authentication, None, None, None, &[])?;
+        Ok(options::PublicKeyCredentialRequestOptions {
+            challenge,
+            timeout: timeout_ms.unwrap_or(self.timeout_ms),
+            rp_id: self.rp_id.clone(),
+            allow_credentials: Vec::new(),
+            user_verification: self.user_verification_policy,
+            extensions: options::AuthenticationExtensionsClientInputs::default(),
+        })
+    }
+
+    // Removes the issued challenge matching the one echoed back in the client data, so that it
+    // cannot be used for more than one ceremony, and returns the state of the ceremony it was
+    // issued for. A challenge issued for the other kind of ceremony is removed as well, but
+    // rejected.
+    fn consume_challenge(
+        &mut self,
+        client_data_challenge: &str,
+        ceremony: challenge::Ceremony,
+    ) -> Result<challenge::IssuedChallenge, WebAuthnError> {
+        let challenge = Challenge::from_base64url(client_data_challenge)?;
+        let index = self
+            .challenges
+            .iter()
+            .position(|issued| ct_eq(issued.challenge.as_bytes(), challenge.as_bytes()))
+            .ok_or(WebAuthnError::ChallengeMismatch)?;
+        let issued = self.challenges.remove(index);
+        if issued.ceremony != ceremony {
+            return Err(WebAuthnError::ChallengeMismatch);
+        }
+        if issued.is_expired(self.clock.now(), self.challenge_timeout) {
+            return Err(WebAuthnError::ChallengeExpired);
+        }
+        Ok(issued)
+    }
+
+    // Called once the signature of an assertion has been verified.
+    fn check_replay(&mut self, assertion: &PendingAssertion) -> Result<(), WebAuthnError> {
+        let now = self.clock.now();
+        if let Some(cache) = &mut self.seen_assertions {
+            if cache.check_and_insert(&assertion.credential_id, &assertion.signature, now) {
+                return Err(WebAuthnError::AssertionReplayed);
+            }
+        }
+        Ok(())
+    }
+
+    // Forgets challenges that were issued more than the challenge timeout ago. Expired
+    // challenges are rejected anyway, and are also forgotten whenever a new challenge is issued.
+    pub fn prune_expired_challenges(&mut self) {
+        let now = self.clock.now();
+        let timeout = self.challenge_timeout;
+        self.challenges
+            .retain(|issued| !issued.is_expired(now, timeout));
+    }
+
+    // Runs all registration checks that do not involve the credential store, and returns the
+    // user the new credential is for. The steps are numbered as in the specification; steps 1 to
+    // 4 happen in the browser, and token binding (step 10) is not supported.
+    // See https://www.w3.org/TR/webauthn-2/#sctn-registering-a-new-credential
+    fn verify_registration(
+        &mut self,
+        req: &requests::RegisterRequest,
+    ) -> Result<(UserId, RegistrationResult), WebAuthnError> {
+        debug!("req: {:?}", req);
+        // Steps 5 and 6: parse the client data.
+        let decoded_client_data_json_vec = decode_base64url(&req.response.client_data_json)?;
         let client_data: requests::ClientData =
             serde_json::from_slice(&decoded_client_data_json_vec)
-                .expect("could not parse client data");
-        info!("parsed client data: {:?}", client_data);
-        // See https://w3c.github.io/webauthn/#registering-a-new-credential.
+                .map_err(WebAuthnError::ClientDataParse)?;
+        debug!("parsed client data: {:?}", client_data);
+        // Step 7.
         if client_data.type_ != "webauthn.create" {
-            return false;
+            return Err(WebAuthnError::InvalidClientDataType);
         }
-        if client_data.challenge != "xx" {
-            //return false;
+        // Step 8.
+        let issued =
+            self.consume_challenge(&client_data.challenge, challenge::Ceremony::Registration)?;
+        // Step 9.
+        self.check_origin(&client_data.origin)?;
+        self.check_cross_origin(&client_data)?;
+        // Step 11.
+        let hash = self.hasher.sha256(&decoded_client_data_json_vec);
+        trace!("client data hash: {:?}", hash);
+
+        // Step 12: parse the attestation object and the authenticator data in it.
+        let attestation_object_vec = decode_base64url(&req.response.attestation_object)?;
+        let attestation: requests::Attestation = serde_cbor::from_slice(&attestation_object_vec)
+            .map_err(WebAuthnError::AttestationParse)?;
+        debug!("attestation format: {}", attestation.fmt);
+        let decoded_auth_data = requests::DecodedAuthData::try_from(attestation.auth_data)?;
+        debug!(
+            "auth data: flags {}, counter {}",
+            decoded_auth_data.flags, decoded_auth_data.counter
+        );
+        // Step 13.
+        self.check_rp_id_hash(&decoded_auth_data.rpid_hash, None)?;
+        // Steps 14 and 15.
+        self.check_user_flags(&decoded_auth_data, issued.user_verification)?;
+        // Step 16: the key has to use one of the algorithms offered in the creation options.
+        let attested_credential_data = decoded_auth_data
+            .attested_credential_data
+            .as_ref()
+            .ok_or(WebAuthnError::MissingAttestedCredentialData)?;
+        let alg = attested_credential_data.credential_public_key.alg();
+        if !self.supported_algorithms.contains(&alg) {
+            return Err(WebAuthnError::UnsupportedAlgorithm(alg));
+        }
+        // Step 17: the only extension output that is checked is credProtect. Authenticators that
+        // do not support it leave it out.
+        let credential_protection = decoded_auth_data
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get("credProtect"))
+            .and_then(serde_cbor::Value::as_u64)
+            .and_then(options::CredentialProtectionPolicy::from_level);
+        if let Some((requested, true)) = self.credential_protection {
+            match credential_protection {
+                Some(applied) if applied >= requested => {}
+                _ => return Err(WebAuthnError::CredentialProtectionNotHonored),
+            }
+        }
+        let ep_att = attestation.ep_att;
+        // Steps 18 to 20: verify the attestation statement in its format, up to the trust
+        // anchors.
+        let attestation = attestation::verify_attestation(
+            &attestation.fmt,
+            &attestation.att_stmt,
+            &self.trust_anchors,
+            attestation.auth_data,
+            &decoded_auth_data,
+            &hash,
+        )?;
+        info!("attestation type: {:?}", attestation.attestation_type);
+        // Step 21: decide whether to trust the authenticator model, based on its metadata. An
+        // attestation chain has to lead up to the roots of the model it attests, so authenticators
+        // without metadata, e.g. U2F keys, cannot be attested with a chain at all.
+        if let Some((metadata, policy)) = &self.metadata {
+            if let Some(trust_path) = attestation.attestation_type.trust_path() {
+                metadata
+                    .trust_anchors(&attested_credential_data.aaguid)?
+                    .verify_chain(trust_path)?;
+            }
+            policy.check(metadata, &attested_credential_data.aaguid)?;
         }
-        if client_data.origin != "ll" {
-            //return false;
+        if self.require_aaguid && attested_credential_data.aaguid.is_zero() {
+            return Err(WebAuthnError::MissingAttestation);
         }
-        let mut hasher = sha2::Sha256::new();
-        hasher.input(&decoded_client_data_json_vec);
-        let hash = hasher.result();
-        info!("hash: {:?}", hash);
+        if let Some(allowlist) = &self.enterprise_aaguid_allowlist {
+            if !allowlist.contains(&attested_credential_data.aaguid) {
+                debug!(
+                    "authenticator {} is not on the allowlist",
+                    attested_credential_data.aaguid
+                );
+                return Err(WebAuthnError::AaguidNotAllowed);
+            }
+        }
+        // An enterprise attestation identifies the authenticator through the leaf certificate of
+        // its chain. It is only reported when it was asked for.
+        let leaf = attestation
+            .attestation_type
+            .trust_path()
+            .and_then(|path| path.first());
+        let enterprise_attestation = match leaf {
+            Some(leaf)
+                if ep_att
+                    && self.attestation == options::AttestationConveyancePreference::Enterprise =>
+            {
+                Some(attestation::EnterpriseAttestation::from_certificate(leaf)?)
+            }
+            _ => None,
+        };
+        // Step 22, as far as this ceremony goes: the credential must not be one the user already
+        // has. Credentials of other users are looked up when the credential is stored. The id the
+        // browser reports must also be the one the authenticator attested to.
+        if req.raw_id.as_bytes() != attested_credential_data.credentialid.as_slice() {
+            return Err(WebAuthnError::CredentialIdMismatch);
+        }
+        let credential_id = req.raw_id.clone();
+        if issued.user_credentials.contains(&credential_id) {
+            return Err(WebAuthnError::CredentialExcluded);
+        }
+        // Challenges of discoverable credential logins are not for registering anything.
+        let user_id = issued.user_id.ok_or(WebAuthnError::ChallengeMismatch)?;
+        // Without `creation_options`, the user id doubles as the user handle.
+        let user_handle = issued
+            .user_handle
+            .unwrap_or_else(|| user_id.clone().into_bytes());
+        // Reported by clients that support the credProps extension requested in the options.
+        let resident_key = req
+            .client_extension_results
+            .cred_props
+            .as_ref()
+            .and_then(|cred_props| cred_props.rk);
+        let credential = Credential {
+            id: credential_id,
+            public_key: attested_credential_data.credential_public_key.clone(),
+            counter: decoded_auth_data.counter,
+            aaguid: attested_credential_data.aaguid,
+            user_handle,
+            transports: req.response.transports.clone(),
+            created_at: self.clock.now(),
+            last_used: None,
+            name: None,
+            backup_eligible: decoded_auth_data.backup_eligible,
+            backup_state: decoded_auth_data.backup_state,
+            resident_key,
+        };
+        let result = RegistrationResult {
+            credential,
+            attestation_type: attestation.attestation_type,
+            attestation_details: attestation.details,
+            enterprise_attestation,
+            aaguid: attested_credential_data.aaguid,
+            resident_key,
+            credential_protection,
+            extensions: req.client_extension_results.clone(),
+        };
+        Ok((user_id, result))
+    }
 
-        let attestation_object_vec = base64::decode(&req.response.attestation_object)
-            .expect("could not decode attestation object");
-        let attestation: requests::Attestation = serde_cbor::from_slice(&attestation_object_vec)
-            .expect("coluld not parse attestation object");
-        info!("attestation: {:?}", attestation);
-        let decoded_auth_data: requests::DecodedAuthData = attestation.auth_data.into();
-        info!("auth_data: {:?}", decoded_auth_data);
-        self.credentials.insert(
-            "xxx".to_string(),
-            vec![Credential {
-                id: req.raw_id.clone(),
-            }],
+    // Runs all assertion checks that can be done before the credential is looked up. The steps are
+    // numbered as in the specification. The client data is checked first, so that nothing is
+    // looked up for assertions that do not answer a challenge of ours; steps 6, 7, 20 and 21 are
+    // left to `PendingAssertion`.
+    // See https://www.w3.org/TR/webauthn-2/#sctn-verifying-assertion
+    fn begin_assertion(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<PendingAssertion, WebAuthnError> {
+        debug!("login request: {:?}", req);
+        // Steps 8 to 10: parse the client data.
+        let decoded_client_data_json_vec = decode_base64url(&req.response.client_data_json)?;
+        let client_data: requests::ClientData =
+            serde_json::from_slice(&decoded_client_data_json_vec)
+                .map_err(WebAuthnError::ClientDataParse)?;
+        debug!("client data: {:?}", client_data);
+        // Step 11. Rejecting registration client data here keeps it from being replayed as an
+        // assertion.
+        if client_data.type_ != "webauthn.get" {
+            return Err(WebAuthnError::InvalidClientDataType);
+        }
+        // Step 12.
+        let issued =
+            self.consume_challenge(&client_data.challenge, challenge::Ceremony::Authentication)?;
+        // Step 13.
+        self.check_origin(&client_data.origin)?;
+        self.check_cross_origin(&client_data)?;
+        let authenticator_data = decode_base64url(&req.response.authenticator_data)?;
+        let signature = decode_base64url(&req.response.signature)?;
+        let decoded_auth_data = requests::DecodedAuthData::try_from(authenticator_data.as_slice())?;
+        debug!(
+            "auth data: flags {}, counter {}",
+            decoded_auth_data.flags, decoded_auth_data.counter
         );
-        true
+        // Step 15.
+        // The app id counts only if it was requested in the options for this challenge.
+        let app_id = match &issued.app_id {
+            Some(app_id) if req.client_extension_results.appid == Some(true) => {
+                Some(app_id.as_str())
+            }
+            _ => None,
+        };
+        self.check_rp_id_hash(&decoded_auth_data.rpid_hash, app_id)?;
+        // Steps 16 and 17.
+        self.check_user_flags(&decoded_auth_data, issued.user_verification)?;
+
+        // Step 19.
+        let hash = self.hasher.sha256(&decoded_client_data_json_vec);
+
+        // The signature is computed over the concatenation of the authenticator data, as sent and
+        // so including any extensions, and the hash of the client data.
+        let mut verification_data = authenticator_data;
+        verification_data.extend_from_slice(&hash);
+        let user_handle = match &req.response.user_handle {
+            Some(user_handle) => {
+                let user_handle = decode_base64url(user_handle)?;
+                if user_handle.len() > MAX_USER_HANDLE_LENGTH {
+                    return Err(WebAuthnError::UserHandleTooLong);
+                }
+                Some(user_handle)
+            }
+            None => None,
+        };
+        // Step 5: the credential has to be one of those offered in the allow list. Discoverable
+        // credential logins have no allow list, so any credential of the relying party will do.
+        let credential_id = req.raw_id.clone();
+        if issued.user_id.is_some() && !issued.user_credentials.contains(&credential_id) {
+            return Err(WebAuthnError::CredentialNotAllowed);
+        }
+        Ok(PendingAssertion {
+            user_id: issued.user_id,
+            user_handle,
+            credential_id,
+            counter: decoded_auth_data.counter,
+            user_verified: decoded_auth_data.user_verified,
+            backup_eligible: decoded_auth_data.backup_eligible,
+            backup_state: decoded_auth_data.backup_state,
+            extensions: req.client_extension_results.clone(),
+            signature,
+            verification_data,
+        })
+    }
+}
+
+// What is left to check of an assertion once the credential has been looked up.
+struct PendingAssertion {
+    user_id: Option<UserId>,
+    user_handle: Option<Vec<u8>>,
+    credential_id: CredentialId,
+    counter: u32,
+    user_verified: bool,
+    backup_eligible: bool,
+    backup_state: bool,
+    extensions: requests::AuthenticationExtensionsClientOutputs,
+    signature: Vec<u8>,
+    verification_data: Vec<u8>,
+}
+
+impl PendingAssertion {
+    // Step 6: the authenticator returns the user handle of discoverable credentials, which has to
+    // be the one the credential was registered with. Discoverable credential logins depend on it
+    // to tell who is logging in.
+    fn check_user_handle(&self, credential: &Credential) -> Result<(), WebAuthnError> {
+        match &self.user_handle {
+            Some(user_handle) if *user_handle != credential.user_handle => {
+                Err(WebAuthnError::CredentialNotFound)
+            }
+            None if self.user_id.is_none() => Err(WebAuthnError::MissingUserHandle),
+            _ => Ok(()),
+        }
+    }
+
+    // Checks the signature with the stored `credential`, and returns the credential with its new
+    // counter, backup state and last use.
+    fn finish(
+        &self,
+        mut credential: Credential,
+        now: std::time::SystemTime,
+    ) -> Result<Credential, WebAuthnError> {
+        // Step 20.
+        if !credential
+            .public_key
+            .verify_signature(&self.signature, &self.verification_data)?
+        {
+            return Err(WebAuthnError::InvalidAssertionSignature);
+        }
+
+        // Step 21: a counter that does not increase means that the credential may have been cloned.
+        // Authenticators that do not implement a counter always report zero.
+        // See https://w3c.github.io/webauthn/#signature-counter
+        if (self.counter != 0 || credential.counter != 0) && self.counter <= credential.counter {
+            return Err(WebAuthnError::CounterRollback);
+        }
+        // Backup eligibility is fixed when the credential is created; only the backup state may
+        // change later.
+        // See https://w3c.github.io/webauthn/#sctn-credential-backup
+        if self.backup_eligible != credential.backup_eligible {
+            return Err(WebAuthnError::BackupEligibilityChanged);
+        }
+        credential.counter = self.counter;
+        credential.backup_state = self.backup_state;
+        credential.last_used = Some(now);
+        Ok(credential)
+    }
+
+    fn result(self, user_id: UserId, credential: Credential) -> AssertionResult {
+        AssertionResult {
+            credential_id: credential.id,
+            user_id,
+            user_handle: credential.user_handle,
+            counter: credential.counter,
+            user_verified: self.user_verified,
+            backup_state: credential.backup_state,
+            extensions: self.extensions,
+        }
+    }
+}
+
+fn credential_descriptors(
+    credentials: Vec<Credential>,
+) -> Vec<options::PublicKeyCredentialDescriptor> {
+    credentials
+        .into_iter()
+        .map(|c| options::PublicKeyCredentialDescriptor {
+            type_: "public-key".to_string(),
+            id: c.id,
+            transports: c.transports,
+        })
+        .collect()
+}
+
+impl<S: CredentialStore> WebAuthn<S> {
+    // Issues a challenge for registering a credential for `user_id`, which also becomes the user
+    // handle of the credential and so must be at most 64 bytes.
+    // See https://developer.mozilla.org/en-US/docs/Web/API/Web_Authentication_API
+    // https://w3c.github.io/webauthn/#registering-a-new-credential
+    pub fn generate_challenge(&mut self, user_id: UserId) -> Result<Challenge, WebAuthnError> {
+        let existing = self.credentials.find(&user_id);
+        self.registration_challenge(user_id, &existing)
+    }
+
+    // Issues a challenge for registering a new credential for `username`, and returns the
+    // options to pass to `navigator.credentials.create()`. `user_id` becomes the user handle of
+    // the credential and must be at most 64 bytes; it should not contain personal information.
+    // `timeout_ms` overrides the configured timeout for this ceremony.
+    // See https://w3c.github.io/webauthn/#sctn-sample-registration
+    pub fn creation_options(
+        &mut self,
+        user_id: Vec<u8>,
+        username: String,
+        display_name: String,
+        timeout_ms: Option<u32>,
+    ) -> Result<options::PublicKeyCredentialCreationOptions, WebAuthnError> {
+        let existing = self.credentials.find(&username);
+        self.build_creation_options(user_id, username, display_name, timeout_ms, existing)
+    }
+
+    // Issues a challenge for authenticating `username` with one of their registered credentials,
+    // and returns the options to pass to `navigator.credentials.get()`. `timeout_ms` overrides the
+    // configured timeout for this ceremony.
+    // See https://w3c.github.io/webauthn/#sctn-sample-authentication
+    pub fn request_options(
+        &mut self,
+        username: String,
+        timeout_ms: Option<u32>,
+    ) -> Result<options::PublicKeyCredentialRequestOptions, WebAuthnError> {
+        let existing = self.credentials.find(&username);
+        self.build_request_options(username, timeout_ms, existing)
+    }
+
+    pub fn get_credentials(&self, user_id: UserId) -> Vec<Credential> {
+        self.credentials.find(&user_id)
+    }
+
+    pub fn list_credentials(&self, username: &str) -> Vec<CredentialInfo> {
+        self.credentials
+            .find(username)
+            .iter()
+            .map(CredentialInfo::from)
+            .collect()
+    }
+
+    // Deregisters a credential of `username`, e.g. for a lost security key. Returns whether
+    // there was such a credential.
+    pub fn remove_credential(&mut self, username: &str, credential_id: &CredentialId) -> bool {
+        self.credentials.remove(username, credential_id)
+    }
+
+    // Removes the credentials that have been idle for longer than the configured maximum, and
+    // returns them as (user, credential id) pairs, e.g. to notify their users. Meant to be called
+    // periodically.
+    pub fn expire_idle_credentials(&mut self) -> Vec<(UserId, CredentialId)> {
+        let max_idle = match self.credential_max_idle {
+            Some(max_idle) => max_idle,
+            None => return Vec::new(),
+        };
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        for (user, credential) in self.credentials.find_all() {
+            if credential.is_idle(now, max_idle) && self.credentials.remove(&user, &credential.id) {
+                expired.push((user, credential.id));
+            }
+        }
+        expired
+    }
+
+    // Sets the name shown for a credential of `username`, or clears it with `None`. Returns
+    // whether there was such a credential.
+    pub fn rename_credential(
+        &mut self,
+        username: &str,
+        credential_id: &CredentialId,
+        new_name: Option<String>,
+    ) -> bool {
+        match self
+            .credentials
+            .find(username)
+            .into_iter()
+            .find(|c| c.id == *credential_id)
+        {
+            Some(mut credential) => {
+                credential.name = new_name;
+                self.credentials.update(&credential);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        req: &requests::RegisterRequest,
+    ) -> Result<RegistrationResult, WebAuthnError> {
+        let (user_id, result) = self.verify_registration(req)?;
+        // Step 22: no other user may have registered the credential.
+        if self.credentials.find_by_id(&result.credential.id).is_some() {
+            return Err(WebAuthnError::CredentialAlreadyRegistered);
+        }
+        // Step 23.
+        self.credentials.save(&user_id, &result.credential);
+        Ok(result)
     }
 
     // See:
     // - https://w3c.github.io/webauthn/#verifying-assertion
-    pub fn verify(&mut self, req: &requests::LoginRequest) -> bool {
-        info!("login request: {:?}", req);
-        let decoded_client_data_json_vec =
-            base64::decode(&req.response.client_data_json).expect("could not convert client data");
-        let client_data: requests::ClientData =
-            serde_json::from_slice(&decoded_client_data_json_vec)
-                .expect("could not parse client data");
-        info!("client data: {:?}", client_data);
-        false
+    pub fn verify(&mut self, req: &requests::LoginRequest) -> Result<bool, WebAuthnError> {
+        self.verify_discoverable(req)
+            .map(|user_id| user_id.is_some())
+    }
+
+    // Like `verify`, but also accepts logins started with `request_options_discoverable`.
+    // Returns the user handle of the user that logged in, or `None` if the signature is invalid.
+    pub fn verify_discoverable(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<Option<Vec<u8>>, WebAuthnError> {
+        match self.verify_assertion(req) {
+            Ok(result) => Ok(Some(result.user_handle)),
+            Err(WebAuthnError::InvalidAssertionSignature) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Runs the whole assertion ceremony, for logins started with either `request_options` or
+    // `request_options_discoverable`, and reports who logged in. An invalid signature is an
+    // `InvalidAssertionSignature` error.
+    pub fn verify_assertion(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<AssertionResult, WebAuthnError> {
+        let assertion = self.begin_assertion(req)?;
+        // Step 7: look up the public key, and the user it belongs to for discoverable logins.
+        let (user_id, credential) = self
+            .credentials
+            .find_credential(&assertion.credential_id)
+            .ok_or(WebAuthnError::CredentialNotFound)?;
+        // Step 6: the credential has to belong to the user the challenge was issued for, if any.
+        if let Some(expected) = &assertion.user_id {
+            if *expected != user_id {
+                return Err(WebAuthnError::CredentialNotFound);
+            }
+        }
+        assertion.check_user_handle(&credential)?;
+        let credential = assertion.finish(credential, self.clock.now())?;
+        self.check_replay(&assertion)?;
+        self.credentials.update(&credential);
+        Ok(assertion.result(user_id, credential))
+    }
+}
+
+// The same ceremonies for credential stores that are accessed asynchronously.
+#[cfg(feature = "tokio")]
+impl<S: AsyncCredentialStore> WebAuthn<S> {
+    pub async fn generate_challenge_async(
+        &mut self,
+        user_id: UserId,
+    ) -> Result<Challenge, WebAuthnError> {
+        let existing = self.credentials.find(&user_id).await;
+        self.registration_challenge(user_id, &existing)
+    }
+
+    pub async fn creation_options_async(
+        &mut self,
+        user_id: Vec<u8>,
+        username: String,
+        display_name: String,
+        timeout_ms: Option<u32>,
+    ) -> Result<options::PublicKeyCredentialCreationOptions, WebAuthnError> {
+        let existing = self.credentials.find(&username).await;
+        self.build_creation_options(user_id, username, display_name, timeout_ms, existing)
+    }
+
+    pub async fn request_options_async(
+        &mut self,
+        username: String,
+        timeout_ms: Option<u32>,
+    ) -> Result<options::PublicKeyCredentialRequestOptions, WebAuthnError> {
+        let existing = self.credentials.find(&username).await;
+        self.build_request_options(username, timeout_ms, existing)
+    }
+
+    pub async fn get_credentials_async(&self, user_id: UserId) -> Vec<Credential> {
+        self.credentials.find(&user_id).await
+    }
+
+    pub async fn list_credentials_async(&self, username: &str) -> Vec<CredentialInfo> {
+        self.credentials
+            .find(username)
+            .await
+            .iter()
+            .map(CredentialInfo::from)
+            .collect()
+    }
+
+    pub async fn remove_credential_async(
+        &mut self,
+        username: &str,
+        credential_id: &CredentialId,
+    ) -> bool {
+        self.credentials.remove(username, credential_id).await
+    }
+
+    pub async fn expire_idle_credentials_async(&mut self) -> Vec<(UserId, CredentialId)> {
+        let max_idle = match self.credential_max_idle {
+            Some(max_idle) => max_idle,
+            None => return Vec::new(),
+        };
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        for (user, credential) in self.credentials.find_all().await {
+            if credential.is_idle(now, max_idle)
+                && self.credentials.remove(&user, &credential.id).await
+            {
+                expired.push((user, credential.id));
+            }
+        }
+        expired
+    }
+
+    pub async fn rename_credential_async(
+        &mut self,
+        username: &str,
+        credential_id: &CredentialId,
+        new_name: Option<String>,
+    ) -> bool {
+        match self
+            .credentials
+            .find(username)
+            .await
+            .into_iter()
+            .find(|c| c.id == *credential_id)
+        {
+            Some(mut credential) => {
+                credential.name = new_name;
+                self.credentials.update(&credential).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn register_async(
+        &mut self,
+        req: &requests::RegisterRequest,
+    ) -> Result<RegistrationResult, WebAuthnError> {
+        let (user_id, result) = self.verify_registration(req)?;
+        if self
+            .credentials
+            .find_by_id(&result.credential.id)
+            .await
+            .is_some()
+        {
+            return Err(WebAuthnError::CredentialAlreadyRegistered);
+        }
+        self.credentials.save(&user_id, &result.credential).await;
+        Ok(result)
+    }
+
+    pub async fn verify_async(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<bool, WebAuthnError> {
+        self.verify_discoverable_async(req)
+            .await
+            .map(|user_id| user_id.is_some())
+    }
+
+    pub async fn verify_discoverable_async(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<Option<Vec<u8>>, WebAuthnError> {
+        match self.verify_assertion_async(req).await {
+            Ok(result) => Ok(Some(result.user_handle)),
+            Err(WebAuthnError::InvalidAssertionSignature) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn verify_assertion_async(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<AssertionResult, WebAuthnError> {
+        let assertion = self.begin_assertion(req)?;
+        let (user_id, credential) = self
+            .credentials
+            .find_credential(&assertion.credential_id)
+            .await
+            .ok_or(WebAuthnError::CredentialNotFound)?;
+        // The credential has to belong to the user the challenge was issued for, if any.
+        if let Some(expected) = &assertion.user_id {
+            if *expected != user_id {
+                return Err(WebAuthnError::CredentialNotFound);
+            }
+        }
+        assertion.check_user_handle(&credential)?;
+        let credential = assertion.finish(credential, self.clock.now())?;
+        self.check_replay(&assertion)?;
+        self.credentials.update(&credential).await;
+        Ok(assertion.result(user_id, credential))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn registration_request(
+        challenge: &Challenge,
+        key: &TestKey,
+        credential_id: &[u8],
+    ) -> requests::RegisterRequest {
+        let attested = attested_credential_data([0; 16], credential_id, &key.cose);
+        register_request(
+            credential_id,
+            &client_data("webauthn.create", challenge),
+            &none_attestation(&auth_data(RP_ID, UP | AT, 0, &attested)),
+        )
+    }
+
+    #[test]
+    fn challenge_is_consumed() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let challenge = w.generate_challenge("alice".to_string()).unwrap();
+        let req = registration_request(&challenge, &TestKey::es256(), b"credential");
+        w.register(&req).unwrap();
+        assert!(matches!(
+            w.register(&req),
+            Err(WebAuthnError::ChallengeMismatch)
+        ));
+    }
+
+    #[test]
+    fn challenge_not_issued() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        w.generate_challenge("alice".to_string()).unwrap();
+        let challenge = Challenge::new(32).unwrap();
+        let req = registration_request(&challenge, &TestKey::es256(), b"credential");
+        assert!(matches!(
+            w.register(&req),
+            Err(WebAuthnError::ChallengeMismatch)
+        ));
+    }
+
+    #[test]
+    fn registration_rp_id_hash_mismatch() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let challenge = w.generate_challenge("alice".to_string()).unwrap();
+        let key = TestKey::es256();
+        let attested = attested_credential_data([0; 16], b"credential", &key.cose);
+        let mut auth_data = auth_data(RP_ID, UP | AT, 0, &attested);
+        auth_data[0] ^= 1;
+        let req = register_request(
+            b"credential",
+            &client_data("webauthn.create", &challenge),
+            &none_attestation(&auth_data),
+        );
+        assert!(matches!(
+            w.register(&req),
+            Err(WebAuthnError::RpIdHashMismatch(..))
+        ));
+    }
+
+    #[test]
+    fn assertion_rp_id_hash_mismatch() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register(&mut w, "alice", &key, b"credential").unwrap();
+        let options = w.request_options("alice".to_string(), None).unwrap();
+        let mut auth_data = auth_data(RP_ID, UP, 1, &[]);
+        auth_data[0] ^= 1;
+        let client_data = client_data("webauthn.get", &options.challenge);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&sha256(&client_data));
+        let req = login_request(b"credential", &auth_data, &client_data, &key.sign(&signed));
+        assert!(matches!(
+            w.verify_assertion(&req),
+            Err(WebAuthnError::RpIdHashMismatch(..))
+        ));
+    }
+
+    #[test]
+    fn base64url_alphabet() {
+        assert_eq!(decode_base64url("-_8").unwrap(), vec![0xfb, 0xff]);
+        assert_eq!(decode_base64url("-_8=").unwrap(), vec![0xfb, 0xff]);
+        assert!(decode_base64url("+/8").is_err());
+    }
+
+    #[test]
+    fn register_url_safe_credential_id() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let credential_id = [0xfb, 0xff, 0xbf, 0xfe];
+        assert_eq!(b64(&credential_id), "-_-__g");
+        register(&mut w, "alice", &TestKey::es256(), &credential_id).unwrap();
+    }
+
+    #[test]
+    fn counter_rollback() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register(&mut w, "alice", &key, b"credential").unwrap();
+        login(&mut w, "alice", &key, b"credential", UP, 5).unwrap();
+        for counter in &[5, 3] {
+            assert!(matches!(
+                login(&mut w, "alice", &key, b"credential", UP, *counter),
+                Err(WebAuthnError::CounterRollback)
+            ));
+        }
+        login(&mut w, "alice", &key, b"credential", UP, 6).unwrap();
+    }
+
+    #[test]
+    fn counter_always_zero() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register(&mut w, "alice", &key, b"credential").unwrap();
+        login(&mut w, "alice", &key, b"credential", UP, 0).unwrap();
+        login(&mut w, "alice", &key, b"credential", UP, 0).unwrap();
+    }
+
+    #[test]
+    fn exclude_credentials() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        register(&mut w, "alice", &TestKey::es256(), b"credential").unwrap();
+        let options = w
+            .creation_options(
+                b"alice".to_vec(),
+                "alice".to_string(),
+                "Alice".to_string(),
+                None,
+            )
+            .unwrap();
+        let excluded = options
+            .exclude_credentials
+            .iter()
+            .map(|c| c.id.as_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(excluded, vec![&b"credential"[..]]);
+        let options = w
+            .creation_options(b"bob".to_vec(), "bob".to_string(), "Bob".to_string(), None)
+            .unwrap();
+        assert!(options.exclude_credentials.is_empty());
+    }
+
+    #[test]
+    fn register_with_login_challenge() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let options = w.request_options("alice".to_string(), None).unwrap();
+        let req = registration_request(&options.challenge, &TestKey::es256(), b"credential");
+        assert!(matches!(
+            w.register(&req),
+            Err(WebAuthnError::ChallengeMismatch)
+        ));
+    }
+
+    #[test]
+    fn login_with_registration_challenge() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register(&mut w, "alice", &key, b"credential").unwrap();
+        let challenge = w.generate_challenge("alice".to_string()).unwrap();
+        let auth_data = auth_data(RP_ID, UP, 1, &[]);
+        let client_data = client_data("webauthn.get", &challenge);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&sha256(&client_data));
+        let req = login_request(b"credential", &auth_data, &client_data, &key.sign(&signed));
+        assert!(matches!(
+            w.verify_assertion(&req),
+            Err(WebAuthnError::ChallengeMismatch)
+        ));
+    }
+
+    #[test]
+    fn constant_time_eq() {
+        assert!(ct_eq(b"challenge", b"challenge"));
+        assert!(!ct_eq(b"challenge", b"challengf"));
+        assert!(!ct_eq(b"challenge", b"challeng"));
+        assert!(ct_eq(b"", b""));
+    }
+
+    // Registers a credential with packed attestation, by an authenticator of model `aaguid` whose
+    // attestation certificate is issued by `ca`.
+    fn register_packed(
+        w: &mut WebAuthn,
+        aaguid: &str,
+        ca: &TestKey,
+    ) -> Result<RegistrationResult, WebAuthnError> {
+        let challenge = w.generate_challenge("alice".to_string()).unwrap();
+        let client_data = client_data("webauthn.create", &challenge);
+        let aaguid = aaguid.parse::<requests::Aaguid>().unwrap();
+        let key = TestKey::es256();
+        let attested = attested_credential_data(aaguid.0, b"credential", &key.cose);
+        let auth_data = auth_data(RP_ID, UP | AT, 0, &attested);
+        let att_key = TestKey::es256();
+        let subject = [("OU", "Authenticator Attestation"), ("CN", "Authenticator")];
+        let cert = certificate(&att_key, &subject, ca, &[]);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&sha256(&client_data));
+        let att_stmt = cbor_map(vec![
+            (text("alg"), serde_cbor::Value::I64(cose::ALG_ES256)),
+            (text("sig"), serde_cbor::Value::Bytes(att_key.sign(&signed))),
+            (text("x5c"), x5c(&[&cert])),
+        ]);
+        let attestation_object = attestation_object("packed", att_stmt, &auth_data);
+        w.register(&register_request(
+            b"credential",
+            &client_data,
+            &attestation_object,
+        ))
+    }
+
+    #[test]
+    fn metadata_roots_of_attested_model() {
+        const AAGUID_A: &str = "cb69481e-8ff7-4039-93ec-0a2729a154a8";
+        const AAGUID_B: &str = "ee882879-721c-4913-9775-3dfcce97072a";
+        let ca_a = TestKey::es256();
+        let ca_b = TestKey::es256();
+        let blob = metadata_blob(&[
+            (AAGUID_A, &root_certificate(&ca_a)),
+            (AAGUID_B, &root_certificate(&ca_b)),
+        ]);
+        let metadata =
+            metadata::MetadataService::from_blob(&blob, &TrustAnchorStore::no_trust_required())
+                .unwrap();
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        w.set_metadata(metadata, metadata::MetadataPolicy::RequireCertified);
+        register_packed(&mut w, AAGUID_A, &ca_a).unwrap();
+        // A chain of another known model, and a model without metadata.
+        assert!(matches!(
+            register_packed(&mut w, AAGUID_A, &ca_b),
+            Err(WebAuthnError::UntrustedAttestation)
+        ));
+        assert!(matches!(
+            register_packed(&mut w, "00000000-0000-0000-0000-000000000001", &ca_a),
+            Err(WebAuthnError::UntrustedAttestation)
+        ));
+    }
+
+    // An assertion of a credential that was registered through the U2F API under `app_id`.
+    fn app_id_assertion(
+        key: &TestKey,
+        challenge: &Challenge,
+        app_id: &str,
+        app_id_used: bool,
+    ) -> requests::LoginRequest {
+        let auth_data = auth_data(app_id, UP, 1, &[]);
+        let client_data = client_data("webauthn.get", challenge);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&sha256(&client_data));
+        let mut req = login_request(b"credential", &auth_data, &client_data, &key.sign(&signed));
+        req.client_extension_results.appid = Some(app_id_used);
+        req
+    }
+
+    #[test]
+    fn app_id() {
+        const APP_ID: &str = "https://example.com/app-id.json";
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register(&mut w, "alice", &key, b"credential").unwrap();
+
+        let challenge = w
+            .request_options("alice".to_string(), None)
+            .unwrap()
+            .challenge;
+        w.set_app_id(Some(APP_ID.to_string()));
+        // The app id was not requested with this challenge.
+        let req = app_id_assertion(&key, &challenge, APP_ID, true);
+        assert!(matches!(
+            w.verify_assertion(&req),
+            Err(WebAuthnError::RpIdHashMismatch(..))
+        ));
+
+        let options = w.request_options("alice".to_string(), None).unwrap();
+        assert_eq!(options.extensions.appid.as_deref(), Some(APP_ID));
+        let req = app_id_assertion(&key, &options.challenge, APP_ID, false);
+        assert!(matches!(
+            w.verify_assertion(&req),
+            Err(WebAuthnError::RpIdHashMismatch(..))
+        ));
+        let challenge = w
+            .request_options("alice".to_string(), None)
+            .unwrap()
+            .challenge;
+        let req = app_id_assertion(&key, &challenge, APP_ID, true);
+        w.verify_assertion(&req).unwrap();
+    }
+
+    #[test]
+    fn backup_state() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        let registered =
+            register_with_flags(&mut w, "alice", &key, b"credential", UP | BE).unwrap();
+        assert!(registered.credential.backup_eligible);
+        assert!(!registered.credential.backup_state);
+
+        let result = login(&mut w, "alice", &key, b"credential", UP | BE | BS, 1).unwrap();
+        assert!(result.backup_state);
+        let credential = &w.get_credentials("alice".to_string())[0];
+        assert!(credential.backup_eligible);
+        assert!(credential.backup_state);
+
+        // A synced credential may stop being backed up.
+        let result = login(&mut w, "alice", &key, b"credential", UP | BE, 2).unwrap();
+        assert!(!result.backup_state);
+        assert!(!w.get_credentials("alice".to_string())[0].backup_state);
+    }
+
+    #[test]
+    fn backup_eligibility_changed() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register_with_flags(&mut w, "alice", &key, b"synced", UP | BE).unwrap();
+        register(&mut w, "alice", &key, b"device-bound").unwrap();
+        for (credential_id, flags) in &[(&b"synced"[..], UP), (&b"device-bound"[..], UP | BE)] {
+            assert!(matches!(
+                login(&mut w, "alice", &key, credential_id, *flags, 1),
+                Err(WebAuthnError::BackupEligibilityChanged)
+            ));
+        }
+        assert!(matches!(
+            login(&mut w, "alice", &key, b"device-bound", UP | BS, 1),
+            Err(WebAuthnError::InvalidBackupFlags)
+        ));
+    }
+
+    // Issues the same challenge every time, so that an assertion can be replayed with a challenge
+    // that is still outstanding.
+    #[derive(Debug)]
+    struct FixedRng;
+
+    impl ChallengeRng for FixedRng {
+        fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), WebAuthnError> {
+            dest.iter_mut().for_each(|b| *b = 7);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replayed_assertion() {
+        let key = TestKey::es256();
+        let replay = |w: &mut WebAuthn| {
+            w.set_challenge_rng(FixedRng);
+            register(w, "alice", &key, b"credential").unwrap();
+            let challenge = w
+                .request_options("alice".to_string(), None)
+                .unwrap()
+                .challenge;
+            // The authenticator has no signature counter.
+            let auth_data = auth_data(RP_ID, UP, 0, &[]);
+            let client_data = client_data("webauthn.get", &challenge);
+            let mut signed = auth_data.clone();
+            signed.extend_from_slice(&sha256(&client_data));
+            let req = login_request(b"credential", &auth_data, &client_data, &key.sign(&signed));
+            w.verify_assertion(&req).unwrap();
+            w.request_options("alice".to_string(), None).unwrap();
+            w.verify_assertion(&req)
+        };
+
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        replay(&mut w).unwrap();
+
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        w.set_seen_assertion_cache(InMemoryAssertionCache::new(std::time::Duration::from_secs(
+            60,
+        )));
+        assert!(matches!(
+            replay(&mut w),
+            Err(WebAuthnError::AssertionReplayed)
+        ));
+    }
+
+    #[test]
+    fn credential_id_lengths() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        for &len in &[16, 64, 1023] {
+            let credential_id = vec![len as u8; len];
+            register(&mut w, "alice", &key, &credential_id).unwrap();
+            let result = login(&mut w, "alice", &key, &credential_id, UP, 1).unwrap();
+            assert_eq!(result.credential_id, CredentialId::from(credential_id));
+        }
+    }
+
+    #[test]
+    fn client_data_with_extra_members() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register(&mut w, "alice", &key, b"credential").unwrap();
+        let challenge = w
+            .request_options("alice".to_string(), None)
+            .unwrap()
+            .challenge;
+        // Members in another order, with whitespace and members the library does not know. The
+        // signature only verifies if the hash is taken over these exact bytes.
+        let client_data = format!(
+            r#"{{ "origin": "{}", "crossOrigin": false, "type": "webauthn.get",
+                "challenge": "{}", "other_keys_can_be_added_here": "do not compare" }}"#,
+            ORIGIN,
+            challenge.as_base64url()
+        )
+        .into_bytes();
+        let auth_data = auth_data(RP_ID, UP, 1, &[]);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&sha256(&client_data));
+        let req = login_request(b"credential", &auth_data, &client_data, &key.sign(&signed));
+        w.verify_assertion(&req).unwrap();
+    }
+
+    #[test]
+    fn unknown_attestation_format() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        let attested = attested_credential_data([0; 16], b"credential", &key.cose);
+        let auth_data = auth_data(RP_ID, UP | AT, 0, &attested);
+
+        let challenge = w.generate_challenge("alice".to_string()).unwrap();
+        let attestation = attestation_object("tpm-garbage", cbor_map(Vec::new()), &auth_data);
+        match w.register(&register_request(
+            b"credential",
+            &client_data("webauthn.create", &challenge),
+            &attestation,
+        )) {
+            Err(WebAuthnError::UnsupportedAttestationFormat(fmt)) => assert_eq!(fmt, "tpm-garbage"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let challenge = w.generate_challenge("alice".to_string()).unwrap();
+        let attestation = serde_cbor::to_vec(&cbor_map(vec![
+            (text("attStmt"), cbor_map(Vec::new())),
+            (text("authData"), serde_cbor::Value::Bytes(auth_data)),
+        ]))
+        .unwrap();
+        assert!(matches!(
+            w.register(&register_request(
+                b"credential",
+                &client_data("webauthn.create", &challenge),
+                &attestation,
+            )),
+            Err(WebAuthnError::AttestationParse(_))
+        ));
+        assert!(w.get_credentials("alice".to_string()).is_empty());
+    }
+
+    #[test]
+    fn assertion_with_extensions() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let key = TestKey::es256();
+        register(&mut w, "alice", &key, b"credential").unwrap();
+        let extensions = cbor_map(vec![(
+            text("hmac-secret"),
+            serde_cbor::Value::Bytes(vec![1; 32]),
+        )]);
+        let auth_data = auth_data(RP_ID, UP | ED, 1, &serde_cbor::to_vec(&extensions).unwrap());
+        for &signed_len in &[37, auth_data.len()] {
+            let challenge = w
+                .request_options("alice".to_string(), None)
+                .unwrap()
+                .challenge;
+            let client_data = client_data("webauthn.get", &challenge);
+            // The signature covers the whole authenticator data, extensions included.
+            let mut signed = auth_data[..signed_len].to_vec();
+            signed.extend_from_slice(&sha256(&client_data));
+            let req = login_request(b"credential", &auth_data, &client_data, &key.sign(&signed));
+            let result = w.verify_assertion(&req);
+            if signed_len == auth_data.len() {
+                assert_eq!(result.unwrap().counter, 1);
+            } else {
+                assert!(matches!(
+                    result,
+                    Err(WebAuthnError::InvalidAssertionSignature)
+                ));
+            }
+        }
+    }
+
+    // A clock the test moves forward by hand.
+    #[derive(Debug, Clone)]
+    struct TestClock(std::sync::Arc<std::sync::Mutex<std::time::SystemTime>>);
+
+    impl TestClock {
+        fn new() -> Self {
+            TestClock(std::sync::Arc::new(std::sync::Mutex::new(
+                std::time::SystemTime::UNIX_EPOCH,
+            )))
+        }
+
+        fn advance(&self, by: std::time::Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> std::time::SystemTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn expired_challenges_are_pruned() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        let clock = TestClock::new();
+        w.set_clock(clock.clone());
+        for _ in 0..1000 {
+            w.request_options_discoverable(None).unwrap();
+        }
+        assert_eq!(w.challenges.len(), 1000);
+        clock.advance(w.challenge_timeout + std::time::Duration::from_secs(1));
+        w.request_options_discoverable(None).unwrap();
+        assert_eq!(w.challenges.len(), 1);
+    }
+
+    #[test]
+    fn outstanding_challenges_are_capped() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        w.set_max_challenges(100);
+        let key = TestKey::es256();
+        let oldest = w.generate_challenge("alice".to_string()).unwrap();
+        for _ in 0..150 {
+            w.request_options_discoverable(None).unwrap();
+        }
+        let newest = w.generate_challenge("alice".to_string()).unwrap();
+        assert_eq!(w.challenges.len(), 100);
+        assert!(matches!(
+            w.register(&registration_request(&oldest, &key, b"credential")),
+            Err(WebAuthnError::ChallengeMismatch)
+        ));
+        w.register(&registration_request(&newest, &key, b"credential"))
+            .unwrap();
+    }
+
+    #[test]
+    fn user_id_too_long() {
+        let mut w = WebAuthn::new(RP_ID.to_string());
+        assert!(matches!(
+            w.generate_challenge("a".repeat(65)),
+            Err(WebAuthnError::UserHandleTooLong)
+        ));
+        assert!(w.challenges.is_empty());
+        let challenge = w.generate_challenge("a".repeat(64)).unwrap();
+        let result = w
+            .register(&registration_request(
+                &challenge,
+                &TestKey::es256(),
+                b"credential",
+            ))
+            .unwrap();
+        assert_eq!(result.credential.user_handle, vec![b'a'; 64]);
     }
 }
@@ -0,0 +1,302 @@
+// Authenticator metadata from the FIDO Metadata Service (MDS), looked up by the AAGUID attested at
+// registration.
+// See https://fidoalliance.org/specs/mds/fido-metadata-service-v3.0-ps-20210518.html
+
+use crate::requests::Aaguid;
+use crate::{attestation, TrustAnchorStore, WebAuthnError};
+use std::collections::HashMap;
+
+// See https://fidoalliance.org/specs/mds/fido-metadata-service-v3.0-ps-20210518.html#authenticatorstatus-enum
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuthenticatorStatus {
+    NotFidoCertified,
+    FidoCertified,
+    UserVerificationBypass,
+    AttestationKeyCompromise,
+    UserKeyRemoteCompromise,
+    UserKeyPhysicalCompromise,
+    UpdateAvailable,
+    Revoked,
+    SelfAssertionSubmitted,
+    #[serde(rename = "FIDO_CERTIFIED_L1")]
+    FidoCertifiedL1,
+    #[serde(rename = "FIDO_CERTIFIED_L1plus")]
+    FidoCertifiedL1Plus,
+    #[serde(rename = "FIDO_CERTIFIED_L2")]
+    FidoCertifiedL2,
+    #[serde(rename = "FIDO_CERTIFIED_L2plus")]
+    FidoCertifiedL2Plus,
+    #[serde(rename = "FIDO_CERTIFIED_L3")]
+    FidoCertifiedL3,
+    #[serde(rename = "FIDO_CERTIFIED_L3plus")]
+    FidoCertifiedL3Plus,
+    // A status added to the specification after this was written.
+    #[serde(other)]
+    Unknown,
+}
+
+impl AuthenticatorStatus {
+    // Whether the authenticator, or its attestation key, can no longer be relied on.
+    pub fn is_compromised(self) -> bool {
+        matches!(
+            self,
+            AuthenticatorStatus::UserVerificationBypass
+                | AuthenticatorStatus::AttestationKeyCompromise
+                | AuthenticatorStatus::UserKeyRemoteCompromise
+                | AuthenticatorStatus::UserKeyPhysicalCompromise
+                | AuthenticatorStatus::Revoked
+        )
+    }
+
+    pub fn is_certified(self) -> bool {
+        matches!(
+            self,
+            AuthenticatorStatus::FidoCertified
+                | AuthenticatorStatus::FidoCertifiedL1
+                | AuthenticatorStatus::FidoCertifiedL1Plus
+                | AuthenticatorStatus::FidoCertifiedL2
+                | AuthenticatorStatus::FidoCertifiedL2Plus
+                | AuthenticatorStatus::FidoCertifiedL3
+                | AuthenticatorStatus::FidoCertifiedL3Plus
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusReport {
+    pub status: AuthenticatorStatus,
+    // ISO 8601 date, e.g. `2021-05-18`.
+    pub effective_date: Option<String>,
+    pub certificate_number: Option<String>,
+}
+
+// The parts of a metadata statement that are useful to relying parties. Everything else in the
+// statement is ignored.
+// See https://fidoalliance.org/specs/mds/fido-metadata-statement-v3.0-ps-20210518.html
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataStatement {
+    pub description: String,
+    pub authenticator_version: u64,
+    pub protocol_family: String,
+    #[serde(default)]
+    pub attestation_types: Vec<String>,
+    // Standard base64 DER certificates that attestations of this model are anchored at.
+    #[serde(default)]
+    pub attestation_root_certificates: Vec<String>,
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataEntry {
+    pub aaguid: Option<String>,
+    pub metadata_statement: Option<MetadataStatement>,
+    // In chronological order.
+    #[serde(default)]
+    pub status_reports: Vec<StatusReport>,
+    pub time_of_last_status_change: Option<String>,
+}
+
+impl MetadataEntry {
+    // The most recent status report.
+    pub fn status(&self) -> Option<AuthenticatorStatus> {
+        self.status_reports.last().map(|report| report.status)
+    }
+
+    // The most recent report that changed whether the authenticator can be relied on. Only that
+    // one counts: a compromise can be resolved by a later certification, and a certification
+    // revoked. An update becoming available, or a status this does not know about, changes
+    // neither.
+    fn standing(&self) -> Option<AuthenticatorStatus> {
+        self.status_reports
+            .iter()
+            .rev()
+            .map(|report| report.status)
+            .find(|status| {
+                !matches!(
+                    status,
+                    AuthenticatorStatus::UpdateAvailable | AuthenticatorStatus::Unknown
+                )
+            })
+    }
+
+    pub fn is_compromised(&self) -> bool {
+        matches!(self.standing(), Some(status) if status.is_compromised())
+    }
+
+    pub fn is_certified(&self) -> bool {
+        matches!(self.standing(), Some(status) if status.is_certified())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataBlobPayload {
+    no: u64,
+    next_update: String,
+    entries: Vec<MetadataEntry>,
+}
+
+// Which authenticators `WebAuthn::register` accepts based on their metadata.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetadataPolicy {
+    // Reject authenticators that the metadata reports as compromised or revoked. Authenticators
+    // without metadata are accepted.
+    RejectCompromised,
+    // Additionally require the authenticator to be FIDO certified, which rules out authenticators
+    // without metadata.
+    RequireCertified,
+}
+
+impl MetadataPolicy {
+    pub(crate) fn check(
+        self,
+        metadata: &MetadataService,
+        aaguid: &Aaguid,
+    ) -> Result<(), WebAuthnError> {
+        let allowed = match (self, metadata.entry(aaguid)) {
+            (_, Some(entry)) if entry.is_compromised() => false,
+            (MetadataPolicy::RequireCertified, Some(entry)) => entry.is_certified(),
+            (MetadataPolicy::RequireCertified, None) => false,
+            (MetadataPolicy::RejectCompromised, _) => true,
+        };
+        if allowed {
+            Ok(())
+        } else {
+            debug!("authenticator {} is not allowed by {:?}", aaguid, self);
+            Err(WebAuthnError::AuthenticatorNotAllowed)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MetadataService {
+    entries: HashMap<Aaguid, MetadataEntry>,
+    serial_number: u64,
+    next_update: String,
+}
+
+impl MetadataService {
+    // Loads the metadata BLOB as downloaded from https://mds3.fidoalliance.org/. It is a JWT
+    // whose chain has to validate up to the FIDO Alliance root in `trust_anchors`. Entries that
+    // are not identified by an AAGUID, i.e. U2F authenticators, are skipped.
+    pub fn from_blob(blob: &str, trust_anchors: &TrustAnchorStore) -> Result<Self, WebAuthnError> {
+        let (payload, _) =
+            attestation::verify_jws(blob.trim(), trust_anchors).map_err(|e| match e {
+                WebAuthnError::InvalidAttestationStatement => WebAuthnError::InvalidMetadata,
+                e => e,
+            })?;
+        let payload: MetadataBlobPayload =
+            serde_json::from_slice(&payload).map_err(|_| WebAuthnError::InvalidMetadata)?;
+        let mut entries = HashMap::new();
+        for entry in payload.entries {
+            if let Some(aaguid) = entry.aaguid.as_ref().and_then(|a| a.parse().ok()) {
+                entries.insert(aaguid, entry);
+            }
+        }
+        info!(
+            "loaded metadata BLOB {} with {} entries",
+            payload.no,
+            entries.len()
+        );
+        Ok(MetadataService {
+            entries,
+            serial_number: payload.no,
+            next_update: payload.next_update,
+        })
+    }
+
+    pub fn entry(&self, aaguid: &Aaguid) -> Option<&MetadataEntry> {
+        self.entries.get(aaguid)
+    }
+
+    pub fn statement(&self, aaguid: &Aaguid) -> Option<&MetadataStatement> {
+        self.entry(aaguid)
+            .and_then(|entry| entry.metadata_statement.as_ref())
+    }
+
+    // The serial number of the loaded BLOB, which increases with every release.
+    pub fn serial_number(&self) -> u64 {
+        self.serial_number
+    }
+
+    // The date by which a newer BLOB will be published, e.g. `2021-06-01`.
+    pub fn next_update(&self) -> &str {
+        &self.next_update
+    }
+
+    // A store requiring trust that holds the attestation roots of the authenticator model
+    // `aaguid`, and only those, so that one model cannot be attested with the roots of another.
+    // Models without metadata have no roots to be trusted with.
+    pub fn trust_anchors(&self, aaguid: &Aaguid) -> Result<TrustAnchorStore, WebAuthnError> {
+        let statement = self.statement(aaguid).ok_or_else(|| {
+            debug!("no metadata statement for authenticator {}", aaguid);
+            WebAuthnError::UntrustedAttestation
+        })?;
+        let mut trust_anchors = TrustAnchorStore::new();
+        for root in &statement.attestation_root_certificates {
+            let der = base64::decode(root).map_err(WebAuthnError::Base64Decode)?;
+            trust_anchors.add_root_der(&der)?;
+        }
+        Ok(trust_anchors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    const AAGUID_A: &str = "cb69481e-8ff7-4039-93ec-0a2729a154a8";
+    const AAGUID_B: &str = "ee882879-721c-4913-9775-3dfcce97072a";
+
+    #[test]
+    fn trust_anchors_per_model() {
+        let root_a = root_certificate(&TestKey::es256());
+        let root_b = root_certificate(&TestKey::es256());
+        let blob = metadata_blob(&[(AAGUID_A, &root_a), (AAGUID_B, &root_b)]);
+        let metadata =
+            MetadataService::from_blob(&blob, &TrustAnchorStore::no_trust_required()).unwrap();
+        let trust_anchors = metadata.trust_anchors(&AAGUID_A.parse().unwrap()).unwrap();
+        assert!(trust_anchors.trust_required());
+        assert_eq!(trust_anchors.roots(), &[root_a]);
+        assert!(matches!(
+            metadata.trust_anchors(&Aaguid([0; 16])),
+            Err(WebAuthnError::UntrustedAttestation)
+        ));
+    }
+
+    fn entry(statuses: &[&str]) -> MetadataEntry {
+        let reports = statuses
+            .iter()
+            .map(|status| serde_json::json!({ "status": status }))
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({
+            "aaguid": AAGUID_A,
+            "statusReports": reports,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn latest_status_counts() {
+        let revoked = entry(&["FIDO_CERTIFIED_L1", "REVOKED"]);
+        assert!(revoked.is_compromised());
+        assert!(!revoked.is_certified());
+
+        let resolved = entry(&["USER_VERIFICATION_BYPASS", "FIDO_CERTIFIED_L1"]);
+        assert!(!resolved.is_compromised());
+        assert!(resolved.is_certified());
+
+        let updated = entry(&["FIDO_CERTIFIED_L1", "UPDATE_AVAILABLE"]);
+        assert!(!updated.is_compromised());
+        assert!(updated.is_certified());
+
+        let unreported = entry(&[]);
+        assert!(!unreported.is_compromised());
+        assert!(!unreported.is_certified());
+    }
+}
@@ -0,0 +1,290 @@
+// Options passed to the browser to start a ceremony, serialized in the shape expected by the
+// WebAuthn JavaScript API. Binary fields are base64url encoded and must be decoded to
+// `ArrayBuffer`s on the client.
+// See https://w3c.github.io/webauthn/#dictionary-makecredentialoptions
+
+use crate::{Challenge, UserVerificationPolicy};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialCreationOptions {
+    pub rp: PublicKeyCredentialRpEntity,
+    pub user: PublicKeyCredentialUserEntity,
+    pub challenge: Challenge,
+    pub pub_key_cred_params: Vec<PublicKeyCredentialParameters>,
+    pub timeout: u32,
+    pub attestation: AttestationConveyancePreference,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authenticator_selection: Option<AuthenticatorSelectionCriteria>,
+    pub exclude_credentials: Vec<PublicKeyCredentialDescriptor>,
+    #[serde(skip_serializing_if = "AuthenticationExtensionsClientInputs::is_empty")]
+    pub extensions: AuthenticationExtensionsClientInputs,
+}
+
+impl PublicKeyCredentialCreationOptions {
+    // The options as JSON, e.g. to embed in a larger response of the application.
+    pub fn to_json_value(&self) -> serde_json::Value {
+        to_json_value(self)
+    }
+}
+
+// The extensions requested from the client. Only those that are set are sent.
+// See https://w3c.github.io/webauthn/#dictdef-authenticationextensionsclientinputs
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsClientInputs {
+    // Asks the client to report whether a new credential is discoverable.
+    // See https://w3c.github.io/webauthn/#sctn-authenticator-credential-properties-extension
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cred_props: Option<bool>,
+    // See https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#sctn-credProtect-extension
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_protection_policy: Option<CredentialProtectionPolicy>,
+    // Makes the client fail the registration if the authenticator cannot apply the policy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enforce_credential_protection_policy: Option<bool>,
+    // Lets logins use credentials registered through the U2F JavaScript API for this app id.
+    // See https://w3c.github.io/webauthn/#sctn-appid-extension
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub appid: Option<String>,
+    // See https://w3c.github.io/webauthn/#sctn-large-blob-extension
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub large_blob: Option<AuthenticationExtensionsLargeBlobInputs>,
+}
+
+impl AuthenticationExtensionsClientInputs {
+    pub fn is_empty(&self) -> bool {
+        self.cred_props.is_none()
+            && self.credential_protection_policy.is_none()
+            && self.enforce_credential_protection_policy.is_none()
+            && self.appid.is_none()
+            && self.large_blob.is_none()
+    }
+}
+
+// Asks for a new credential that can store a blob on its authenticator, or for reading or
+// writing that blob when logging in. Registrations may only set `support`, and logins only one
+// of `read` and `write`.
+// See https://w3c.github.io/webauthn/#dictdef-authenticationextensionslargeblobinputs
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuthenticationExtensionsLargeBlobInputs {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub support: Option<LargeBlobSupport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read: Option<bool>,
+    // The blob to store, base64url encoded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write: Option<String>,
+}
+
+impl AuthenticationExtensionsLargeBlobInputs {
+    pub fn read() -> Self {
+        AuthenticationExtensionsLargeBlobInputs {
+            read: Some(true),
+            ..Default::default()
+        }
+    }
+
+    // Writing replaces the blob of the credential the user logs in with, so the request options
+    // must allow exactly one credential.
+    pub fn write(blob: &[u8]) -> Self {
+        AuthenticationExtensionsLargeBlobInputs {
+            write: Some(crate::encode_base64url(blob)),
+            ..Default::default()
+        }
+    }
+}
+
+// See https://w3c.github.io/webauthn/#enumdef-largeblobsupport
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LargeBlobSupport {
+    Required,
+    Preferred,
+}
+
+// When an authenticator gives out a credential, ordered from least to most protected.
+// See https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#sctn-credProtect-extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CredentialProtectionPolicy {
+    // Level 1: the credential is usable without user verification.
+    UserVerificationOptional,
+    // Level 2: discoverable use requires user verification, use with an allow list does not.
+    #[serde(rename = "userVerificationOptionalWithCredentialIDList")]
+    UserVerificationOptionalWithCredentialIdList,
+    // Level 3: every use requires user verification.
+    UserVerificationRequired,
+}
+
+impl CredentialProtectionPolicy {
+    // The level that authenticators report in the `credProtect` extension output.
+    pub fn from_level(level: u64) -> Option<Self> {
+        match level {
+            1 => Some(CredentialProtectionPolicy::UserVerificationOptional),
+            2 => Some(CredentialProtectionPolicy::UserVerificationOptionalWithCredentialIdList),
+            3 => Some(CredentialProtectionPolicy::UserVerificationRequired),
+            _ => None,
+        }
+    }
+
+    pub fn level(self) -> u64 {
+        match self {
+            CredentialProtectionPolicy::UserVerificationOptional => 1,
+            CredentialProtectionPolicy::UserVerificationOptionalWithCredentialIdList => 2,
+            CredentialProtectionPolicy::UserVerificationRequired => 3,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublicKeyCredentialRpEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialUserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublicKeyCredentialParameters {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub alg: i64,
+}
+
+// Requirements on the authenticator used to create a new credential.
+// See https://w3c.github.io/webauthn/#dictdef-authenticatorselectioncriteria
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelectionCriteria {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authenticator_attachment: Option<AuthenticatorAttachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resident_key: Option<ResidentKeyRequirement>,
+    // Falls back to the user verification policy configured on `WebAuthn` when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_verification: Option<UserVerificationPolicy>,
+}
+
+// See https://w3c.github.io/webauthn/#enumdef-authenticatorattachment
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthenticatorAttachment {
+    Platform,
+    CrossPlatform,
+}
+
+// See https://w3c.github.io/webauthn/#enumdef-residentkeyrequirement
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResidentKeyRequirement {
+    Required,
+    Preferred,
+    Discouraged,
+}
+
+// See https://w3c.github.io/webauthn/#dictionary-assertion-options
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialRequestOptions {
+    pub challenge: Challenge,
+    pub timeout: u32,
+    pub rp_id: String,
+    pub allow_credentials: Vec<PublicKeyCredentialDescriptor>,
+    pub user_verification: UserVerificationPolicy,
+    #[serde(skip_serializing_if = "AuthenticationExtensionsClientInputs::is_empty")]
+    pub extensions: AuthenticationExtensionsClientInputs,
+}
+
+impl PublicKeyCredentialRequestOptions {
+    pub fn to_json_value(&self) -> serde_json::Value {
+        to_json_value(self)
+    }
+}
+
+// The options only contain strings, numbers and structs, which always serialize to JSON.
+fn to_json_value<T: serde::Serialize>(options: &T) -> serde_json::Value {
+    serde_json::to_value(options).expect("options are always representable as JSON")
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublicKeyCredentialDescriptor {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub id: crate::CredentialId,
+    // Lets the browser skip transports the authenticator cannot be reached over.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<AuthenticatorTransport>,
+}
+
+// How the client can communicate with an authenticator.
+// See https://w3c.github.io/webauthn/#enumdef-authenticatortransport
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AuthenticatorTransport {
+    Usb,
+    Nfc,
+    Ble,
+    SmartCard,
+    Hybrid,
+    Internal,
+    // A transport added to the specification after this was written. It is kept, and sent back
+    // to browsers, which ignore transports they do not know either.
+    Unknown(String),
+}
+
+impl AuthenticatorTransport {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuthenticatorTransport::Usb => "usb",
+            AuthenticatorTransport::Nfc => "nfc",
+            AuthenticatorTransport::Ble => "ble",
+            AuthenticatorTransport::SmartCard => "smart-card",
+            AuthenticatorTransport::Hybrid => "hybrid",
+            AuthenticatorTransport::Internal => "internal",
+            AuthenticatorTransport::Unknown(transport) => transport,
+        }
+    }
+}
+
+impl From<String> for AuthenticatorTransport {
+    fn from(transport: String) -> Self {
+        match transport.as_str() {
+            "usb" => AuthenticatorTransport::Usb,
+            "nfc" => AuthenticatorTransport::Nfc,
+            "ble" => AuthenticatorTransport::Ble,
+            "smart-card" => AuthenticatorTransport::SmartCard,
+            "hybrid" => AuthenticatorTransport::Hybrid,
+            "internal" => AuthenticatorTransport::Internal,
+            _ => AuthenticatorTransport::Unknown(transport),
+        }
+    }
+}
+
+impl serde::Serialize for AuthenticatorTransport {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for AuthenticatorTransport {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(AuthenticatorTransport::from)
+    }
+}
+
+// How much the relying party wants to learn about the authenticator that created a credential.
+// See https://w3c.github.io/webauthn/#enumdef-attestationconveyancepreference
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttestationConveyancePreference {
+    None,
+    Indirect,
+    Direct,
+    Enterprise,
+}
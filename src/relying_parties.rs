@@ -0,0 +1,96 @@
+use crate::{
+    requests, AssertionResult, CredentialStore, RegistrationResult, WebAuthn, WebAuthnError,
+};
+use std::collections::HashMap;
+
+// Several relying parties served from one place, e.g. the domains of a multi-tenant service.
+// Each one keeps its own configuration, trust settings and credential store; ceremonies are
+// routed to the relying party whose origins include the origin in the client data, so the
+// origins of different relying parties must not overlap.
+//
+//     let mut relying_parties = RelyingParties::new();
+//     relying_parties.insert(WebAuthn::new("a.example".to_string()));
+//     relying_parties.insert(WebAuthn::new("b.example".to_string()));
+//     if let Some(webauthn) = relying_parties.get_mut("a.example") {
+//         let options = webauthn.request_options("alice".to_string(), None)?;
+//     }
+//     relying_parties.verify(&login_request)?;
+#[derive(Debug)]
+pub struct RelyingParties<S = std::collections::HashMap<crate::UserId, Vec<crate::Credential>>> {
+    relying_parties: HashMap<String, WebAuthn<S>>,
+}
+
+impl<S> Default for RelyingParties<S> {
+    fn default() -> Self {
+        RelyingParties {
+            relying_parties: HashMap::new(),
+        }
+    }
+}
+
+impl<S> RelyingParties<S> {
+    pub fn new() -> Self {
+        RelyingParties::default()
+    }
+
+    // Adds a relying party, replacing and returning any with the same id.
+    pub fn insert(&mut self, webauthn: WebAuthn<S>) -> Option<WebAuthn<S>> {
+        self.relying_parties.insert(webauthn.rp_id(), webauthn)
+    }
+
+    pub fn remove(&mut self, rp_id: &str) -> Option<WebAuthn<S>> {
+        self.relying_parties.remove(rp_id)
+    }
+
+    pub fn get(&self, rp_id: &str) -> Option<&WebAuthn<S>> {
+        self.relying_parties.get(rp_id)
+    }
+
+    pub fn get_mut(&mut self, rp_id: &str) -> Option<&mut WebAuthn<S>> {
+        self.relying_parties.get_mut(rp_id)
+    }
+
+    // The relying party that accepts ceremonies from `origin`.
+    pub fn for_origin_mut(&mut self, origin: &str) -> Option<&mut WebAuthn<S>> {
+        self.relying_parties
+            .values_mut()
+            .find(|webauthn| webauthn.check_origin(origin).is_ok())
+    }
+
+    fn route(&mut self, client_data_json: &str) -> Result<&mut WebAuthn<S>, WebAuthnError> {
+        let client_data: requests::ClientData =
+            serde_json::from_slice(&crate::decode_base64url(client_data_json)?)
+                .map_err(WebAuthnError::ClientDataParse)?;
+        self.for_origin_mut(&client_data.origin)
+            .ok_or(WebAuthnError::OriginMismatch)
+    }
+}
+
+impl<S: CredentialStore> RelyingParties<S> {
+    pub fn register(
+        &mut self,
+        req: &requests::RegisterRequest,
+    ) -> Result<RegistrationResult, WebAuthnError> {
+        self.route(&req.response.client_data_json)?.register(req)
+    }
+
+    pub fn verify(&mut self, req: &requests::LoginRequest) -> Result<bool, WebAuthnError> {
+        self.route(&req.response.client_data_json)?.verify(req)
+    }
+
+    pub fn verify_discoverable(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<Option<Vec<u8>>, WebAuthnError> {
+        self.route(&req.response.client_data_json)?
+            .verify_discoverable(req)
+    }
+
+    pub fn verify_assertion(
+        &mut self,
+        req: &requests::LoginRequest,
+    ) -> Result<AssertionResult, WebAuthnError> {
+        self.route(&req.response.client_data_json)?
+            .verify_assertion(req)
+    }
+}
@@ -0,0 +1,66 @@
+use crate::CredentialId;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+// Remembers assertions that were accepted, so that the exact same assertion is not accepted
+// twice. Signature counters catch most replays, but not those of authenticators without a
+// counter. Deployments with several nodes can share the cache, e.g. through Redis.
+pub trait SeenAssertionCache: std::fmt::Debug + Send + Sync {
+    // Records the assertion made with `signature` by the credential, and returns whether it was
+    // already recorded and has not expired yet.
+    fn check_and_insert(
+        &mut self,
+        credential_id: &CredentialId,
+        signature: &[u8],
+        now: SystemTime,
+    ) -> bool;
+}
+
+// Keeps seen assertions in memory for `ttl`, which should be at least the challenge timeout of
+// the `WebAuthn` it is used with.
+#[derive(Debug, Clone)]
+pub struct InMemoryAssertionCache {
+    ttl: Duration,
+    seen: HashMap<(CredentialId, Vec<u8>), SystemTime>,
+}
+
+impl InMemoryAssertionCache {
+    pub fn new(ttl: Duration) -> Self {
+        InMemoryAssertionCache {
+            ttl,
+            seen: HashMap::new(),
+        }
+    }
+}
+
+impl SeenAssertionCache for InMemoryAssertionCache {
+    fn check_and_insert(
+        &mut self,
+        credential_id: &CredentialId,
+        signature: &[u8],
+        now: SystemTime,
+    ) -> bool {
+        let ttl = self.ttl;
+        self.seen
+            .retain(|_, seen_at| now.duration_since(*seen_at).unwrap_or_default() <= ttl);
+        self.seen
+            .insert((credential_id.clone(), signature.to_vec()), now)
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seen_assertions_expire() {
+        let mut cache = InMemoryAssertionCache::new(Duration::from_secs(60));
+        let id = CredentialId::from(b"credential".to_vec());
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        assert!(!cache.check_and_insert(&id, b"signature", now));
+        assert!(cache.check_and_insert(&id, b"signature", now + Duration::from_secs(60)));
+        assert!(!cache.check_and_insert(&id, b"other signature", now + Duration::from_secs(60)));
+        assert!(!cache.check_and_insert(&id, b"signature", now + Duration::from_secs(121)));
+    }
+}
@@ -1,23 +1,127 @@
+use crate::cose;
+use crate::options::{AuthenticatorAttachment, AuthenticatorTransport};
+use crate::WebAuthnError;
 use byteorder::ByteOrder;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
+const MAX_CREDENTIAL_ID_LENGTH: u16 = 1023;
+
+// The `PublicKeyCredential` returned by `navigator.credentials.create()`, in the shape produced by
+// its `toJSON()` method, i.e. with binary fields base64url encoded. Fields this crate does not
+// use are ignored, unless the `strict` feature is enabled. It only applies to the top level:
+// browsers add members to `response`, e.g. `publicKey`, that are not needed here.
+// See https://w3c.github.io/webauthn/#dictdef-registrationresponsejson
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct RegisterRequest {
     pub id: String,
-    pub raw_id: String,
+    pub raw_id: crate::CredentialId,
     pub response: CredentialsResponse,
     #[serde(rename = "type")]
     pub type_: String,
+    // Browsers that predate the attribute leave it out, and others may send `null`.
+    #[serde(default)]
+    pub authenticator_attachment: Option<AuthenticatorAttachment>,
+    #[serde(default)]
+    pub client_extension_results: AuthenticationExtensionsClientOutputs,
 }
 
-#[derive(Debug, Deserialize)]
+// The outputs of the extensions that were processed by the client, for both registrations and
+// logins. Outputs of extensions not listed here are ignored.
+// See https://w3c.github.io/webauthn/#dictdef-authenticationextensionsclientoutputs
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationExtensionsClientOutputs {
+    pub cred_props: Option<CredentialPropertiesOutput>,
+    // Whether the login used the U2F app id rather than the relying party id.
+    pub appid: Option<bool>,
+    // Whether the new credential supports hmac-secret.
+    // See https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#sctn-hmac-secret-extension
+    pub hmac_create_secret: Option<bool>,
+    pub hmac_get_secret: Option<HmacGetSecretOutput>,
+    pub large_blob: Option<AuthenticationExtensionsLargeBlobOutputs>,
+}
+
+// See https://w3c.github.io/webauthn/#sctn-authenticator-credential-properties-extension
+#[derive(Debug, Clone, Deserialize)]
+pub struct CredentialPropertiesOutput {
+    // Whether the new credential is discoverable.
+    pub rk: Option<bool>,
+}
+
+// The secrets derived from the salts of a login, base64url encoded. `output2` is only present if
+// a second salt was given.
+#[derive(Clone, Deserialize)]
+pub struct HmacGetSecretOutput {
+    pub output1: String,
+    pub output2: Option<String>,
+}
+
+// The outputs are secrets, so they are left out of logs.
+impl std::fmt::Debug for HmacGetSecretOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HmacGetSecretOutput")
+            .field("output1", &"<redacted>")
+            .field("output2", &self.output2.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+// `supported` answers a registration, `blob` a read, base64url encoded, and `written` a write.
+// See https://w3c.github.io/webauthn/#dictdef-authenticationextensionslargebloboutputs
+#[derive(Clone, Deserialize)]
+pub struct AuthenticationExtensionsLargeBlobOutputs {
+    pub supported: Option<bool>,
+    pub blob: Option<String>,
+    pub written: Option<bool>,
+}
+
+impl AuthenticationExtensionsLargeBlobOutputs {
+    pub fn blob_bytes(&self) -> Option<Result<Vec<u8>, WebAuthnError>> {
+        self.blob.as_deref().map(crate::decode_base64url)
+    }
+}
+
+// Blobs are used to store secrets, so they are left out of logs.
+impl std::fmt::Debug for AuthenticationExtensionsLargeBlobOutputs {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("AuthenticationExtensionsLargeBlobOutputs")
+            .field("supported", &self.supported)
+            .field("blob", &self.blob.as_ref().map(|_| "<redacted>"))
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CredentialsResponse {
     pub attestation_object: String,
     #[serde(rename = "clientDataJSON")]
     pub client_data_json: String,
+    // The result of `getTransports()`, which older browsers do not implement.
+    #[serde(default)]
+    pub transports: Vec<AuthenticatorTransport>,
 }
 
+// The attestation object contains the credential public key, so it is left out of logs.
+impl std::fmt::Debug for CredentialsResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CredentialsResponse")
+            .field("attestation_object", &"<redacted>")
+            .field("client_data_json", &self.client_data_json)
+            .field("transports", &self.transports)
+            .finish()
+    }
+}
+
+// The parts of the client data that are checked. Other members, e.g. those added by browsers for
+// their own purposes, are ignored. The client data hash is computed over the bytes as received,
+// never over a reserialization of this.
+// See https://w3c.github.io/webauthn/#dictdef-collectedclientdata
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClientData {
@@ -25,90 +129,589 @@ pub struct ClientData {
     pub type_: String,
     pub challenge: String,
     pub origin: String,
+    // Whether the ceremony ran in an iframe of a different origin, and the origin of the top
+    // level page if so. Older browsers leave both out.
+    #[serde(default)]
+    pub cross_origin: Option<bool>,
+    #[serde(default)]
+    pub top_origin: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Attestation<'a> {
+    // Required, so an attestation object without it fails to parse. Formats that are not
+    // supported are rejected by `attestation::verify_attestation`.
     pub fmt: String,
     //#[serde(with = "serde_bytes")]
     pub auth_data: &'a [u8],
+    pub att_stmt: serde_cbor::Value,
+    // Set by authenticators that returned an enterprise attestation.
+    // See https://fidoalliance.org/specs/fido-v2.1-ps-20210615/fido-client-to-authenticator-protocol-v2.1-ps-20210615.html#sctn-feature-descriptions-enterp-attstn
+    #[serde(default)]
+    pub ep_att: bool,
+}
+
+// The flags byte of the authenticator data.
+// See https://w3c.github.io/webauthn/#authdata-flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u8);
+
+impl Flags {
+    pub fn from_bits(bits: u8) -> Self {
+        Flags(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn user_present(self) -> bool {
+        self.0 & (1 << 0) != 0
+    }
+
+    pub fn user_verified(self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    pub fn backup_eligible(self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    pub fn backup_state(self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+
+    pub fn attested_credential_data_included(self) -> bool {
+        self.0 & (1 << 6) != 0
+    }
+
+    pub fn extension_data_included(self) -> bool {
+        self.0 & (1 << 7) != 0
+    }
+}
+
+// Formats the flags as e.g. `UP=1 UV=1 BE=0 BS=0 AT=1 ED=0`, for logging what an authenticator
+// sent.
+impl std::fmt::Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "UP={} UV={} BE={} BS={} AT={} ED={}",
+            self.user_present() as u8,
+            self.user_verified() as u8,
+            self.backup_eligible() as u8,
+            self.backup_state() as u8,
+            self.attested_credential_data_included() as u8,
+            self.extension_data_included() as u8,
+        )
+    }
 }
 
 #[derive(Debug)]
 pub struct DecodedAuthData {
     pub rpid_hash: Vec<u8>,
+    // The flags as sent; the fields below are decoded from them.
+    pub flags: Flags,
     pub user_present: bool,
     pub user_verified: bool,
+    // Whether the credential can be backed up, e.g. a passkey synced between devices, and whether
+    // it currently is.
+    // See https://w3c.github.io/webauthn/#sctn-credential-backup
+    pub backup_eligible: bool,
+    pub backup_state: bool,
     pub attested_credential_data_included: bool,
     pub extension_data_included: bool,
     pub counter: u32,
-    pub attested_credential_data: AttestedCredentialData,
+    pub attested_credential_data: Option<AttestedCredentialData>,
+    pub extensions: Option<BTreeMap<String, serde_cbor::Value>>,
 }
 
-impl From<&[u8]> for DecodedAuthData {
-    fn from(v: &[u8]) -> Self {
-        let flags = v[32];
-        DecodedAuthData {
+impl TryFrom<&[u8]> for DecodedAuthData {
+    type Error = WebAuthnError;
+
+    fn try_from(v: &[u8]) -> Result<Self, Self::Error> {
+        if v.len() < 37 {
+            return Err(WebAuthnError::AuthDataTooShort);
+        }
+        let flags = Flags::from_bits(v[32]);
+        let backup_eligible = flags.backup_eligible();
+        let backup_state = flags.backup_state();
+        // Only credentials that can be backed up can be backed up.
+        if backup_state && !backup_eligible {
+            return Err(WebAuthnError::InvalidBackupFlags);
+        }
+        let attested_credential_data_included = flags.attested_credential_data_included();
+        let extension_data_included = flags.extension_data_included();
+        // Assertions never carry attested credential data, so only parse it when the authenticator
+        // says it is there.
+        let (attested_credential_data, extensions_offset) = if attested_credential_data_included {
+            let (data, len) = AttestedCredentialData::parse(&v[37..])?;
+            (Some(data), 37 + len)
+        } else {
+            (None, 37)
+        };
+        // Extensions are a CBOR map keyed by extension identifier, following the attested
+        // credential data if there is any.
+        // See https://w3c.github.io/webauthn/#sctn-extensions
+        let extensions = if extension_data_included {
+            Some(
+                serde_cbor::from_slice(&v[extensions_offset..])
+                    .map_err(WebAuthnError::CborParse)?,
+            )
+        } else {
+            // Without extensions, nothing may follow the header or the attested credential data.
+            if extensions_offset != v.len() {
+                return Err(WebAuthnError::TrailingAuthData);
+            }
+            None
+        };
+        Ok(DecodedAuthData {
             rpid_hash: v[0..32].into(),
-            user_present: (flags & (1 << 0)) != 0,
-            user_verified: (flags & (1 << 2)) != 0,
-            attested_credential_data_included: (flags & (1 << 6)) != 0,
-            extension_data_included: (flags & (1 << 7)) != 0,
+            flags,
+            user_present: flags.user_present(),
+            user_verified: flags.user_verified(),
+            backup_eligible,
+            backup_state,
+            attested_credential_data_included,
+            extension_data_included,
             counter: byteorder::BigEndian::read_u32(&v[33..37]),
-            attested_credential_data: v[37..].into(),
+            attested_credential_data,
+            extensions,
+        })
+    }
+}
+
+// The Authenticator Attestation GUID, which identifies the model of an authenticator.
+// See https://w3c.github.io/webauthn/#aaguid
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Aaguid(pub [u8; 16]);
+
+impl Aaguid {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    // Reported by authenticators that do not disclose their model, e.g. with `none` or self
+    // attestation.
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 16]
+    }
+}
+
+// Formats the AAGUID as a UUID, e.g. `cb69481e-8ff7-4039-93ec-0a2729a154a8`, as used by the
+// FIDO metadata service.
+impl std::fmt::Display for Aaguid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i == 4 || i == 6 || i == 8 || i == 10 {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Aaguid {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// Parses an AAGUID in its UUID form, with or without dashes.
+impl std::str::FromStr for Aaguid {
+    type Err = WebAuthnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.replace('-', "");
+        if hex.len() != 32 {
+            return Err(WebAuthnError::InvalidAaguid);
+        }
+        let mut aaguid = [0; 16];
+        for (i, byte) in aaguid.iter_mut().enumerate() {
+            *byte = hex
+                .get(2 * i..2 * i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or(WebAuthnError::InvalidAaguid)?;
+        }
+        Ok(Aaguid(aaguid))
+    }
+}
+
+// Serialized as a UUID string in human-readable formats, as 16 bytes otherwise.
+impl serde::Serialize for Aaguid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string())
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Aaguid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(serde::de::Error::custom)
+        } else {
+            let bytes = serde_bytes::ByteBuf::deserialize(deserializer)?;
+            let mut aaguid = [0; 16];
+            if bytes.len() != aaguid.len() {
+                return Err(serde::de::Error::invalid_length(bytes.len(), &"16 bytes"));
+            }
+            aaguid.copy_from_slice(&bytes);
+            Ok(Aaguid(aaguid))
         }
     }
 }
 
 #[derive(Debug)]
 pub struct AttestedCredentialData {
-    pub aaguid: Vec<u8>,
+    pub aaguid: Aaguid,
     pub credentialid_length: u16,
     pub credentialid: Vec<u8>,
-    //credential_public_key: PublicKey,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct PublicKey {
-    #[serde(rename = "1")]
-    pub key_type: u8,
-    //#[serde(rename = "2")]
-    //type_: u8,
-    //#[serde(rename = "crv")]
-    //curve: u8,
+    pub credential_public_key: cose::CoseKey,
 }
 
-impl From<&[u8]> for AttestedCredentialData {
+impl AttestedCredentialData {
+    // Parses attested credential data from the start of `v` and returns it together with the
+    // number of bytes it occupies. The credential public key has no length prefix, but being a
+    // CBOR map it delimits itself, so it is read from a stream whose position is checked
+    // afterwards.
     // See:
     // - https://w3c.github.io/webauthn/#sec-attested-credential-data
     // - https://developer.mozilla.org/en-US/docs/Web/API/AuthenticatorAssertionResponse/authenticatorData
-    fn from(v: &[u8]) -> Self {
+    fn parse(v: &[u8]) -> Result<(Self, usize), WebAuthnError> {
+        if v.len() < 18 {
+            return Err(WebAuthnError::AuthDataTooShort);
+        }
         let credentialid_length = byteorder::BigEndian::read_u16(&v[16..18]);
-        let public_key_cbor = &v[18 + credentialid_length as usize..];
-        info!("public key cbor: {:?}", public_key_cbor);
-        AttestedCredentialData {
-            aaguid: v[0..16].into(),
-            credentialid_length: credentialid_length,
-            credentialid: v[18..18 + credentialid_length as usize].into(),
-            // See https://w3c.github.io/webauthn/#sctn-encoded-credPubKey-examples
-            //credential_public_key: serde_cbor::from_slice(public_key_cbor)
-            //.expect("could not decode public key"),
+        // The length comes from the authenticator and must neither exceed the data that follows
+        // nor the maximum length of credential ids. Any length in between is valid, e.g. for
+        // authenticators that wrap the private key into the credential id.
+        // See https://w3c.github.io/webauthn/#credential-id
+        if credentialid_length == 0 || credentialid_length > MAX_CREDENTIAL_ID_LENGTH {
+            return Err(WebAuthnError::MalformedCredentialData);
         }
+        let public_key_offset = 18 + credentialid_length as usize;
+        if v.len() < public_key_offset {
+            return Err(WebAuthnError::MalformedCredentialData);
+        }
+        let mut aaguid = [0; 16];
+        aaguid.copy_from_slice(&v[0..16]);
+        let mut reader = std::io::Cursor::new(&v[public_key_offset..]);
+        serde_cbor::Value::deserialize(&mut serde_cbor::Deserializer::from_reader(&mut reader))
+            .map_err(WebAuthnError::CborParse)?;
+        let public_key_length = reader.position() as usize;
+        let public_key_cbor = &v[public_key_offset..public_key_offset + public_key_length];
+        trace!("public key cbor: {} bytes", public_key_cbor.len());
+        let data = AttestedCredentialData {
+            aaguid: Aaguid(aaguid),
+            credentialid_length,
+            credentialid: v[18..public_key_offset].into(),
+            // See https://w3c.github.io/webauthn/#sctn-encoded-credPubKey-examples
+            credential_public_key: cose::CoseKey::try_from(public_key_cbor)?,
+        };
+        Ok((data, public_key_offset + public_key_length))
+    }
+}
+
+impl TryFrom<&[u8]> for AttestedCredentialData {
+    type Error = WebAuthnError;
+
+    fn try_from(v: &[u8]) -> Result<Self, Self::Error> {
+        AttestedCredentialData::parse(v).map(|(data, _)| data)
     }
 }
 
+// The `PublicKeyCredential` returned by `navigator.credentials.get()`, in the shape produced by
+// its `toJSON()` method. Like `RegisterRequest`, it rejects unknown top-level members with the
+// `strict` feature.
+// See https://w3c.github.io/webauthn/#dictdef-authenticationresponsejson
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct LoginRequest {
+    pub id: String,
+    // The credential is looked up by this rather than by `id`, which is the same value.
+    pub raw_id: crate::CredentialId,
     pub response: AuthenticatorAssertionResponse,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub authenticator_attachment: Option<AuthenticatorAttachment>,
+    #[serde(default)]
+    pub client_extension_results: AuthenticationExtensionsClientOutputs,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthenticatorAssertionResponse {
     pub authenticator_data: String,
     #[serde(rename = "clientDataJSON")]
     pub client_data_json: String,
     pub signature: String,
+    // The user handle of the credential, which authenticators return for discoverable
+    // credentials. Browsers send `null` when there is none.
+    #[serde(default)]
+    pub user_handle: Option<String>,
+}
+
+impl std::fmt::Debug for AuthenticatorAssertionResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("AuthenticatorAssertionResponse")
+            .field("authenticator_data", &self.authenticator_data)
+            .field("client_data_json", &self.client_data_json)
+            .field("signature", &"<redacted>")
+            .field("user_handle", &self.user_handle)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn short_auth_data() {
+        for len in 0..37 {
+            assert!(matches!(
+                DecodedAuthData::try_from(&vec![0; len][..]),
+                Err(WebAuthnError::AuthDataTooShort)
+            ));
+        }
+    }
+
+    #[test]
+    fn trailing_auth_data() {
+        let mut v = vec![0; 38];
+        v[32] = UP;
+        assert!(matches!(
+            DecodedAuthData::try_from(v.as_slice()),
+            Err(WebAuthnError::TrailingAuthData)
+        ));
+
+        let key = TestKey::es256();
+        let mut attested = attested_credential_data([0; 16], b"credential", &key.cose);
+        attested.push(0);
+        assert!(matches!(
+            DecodedAuthData::try_from(&auth_data(RP_ID, UP | AT, 0, &attested)[..]),
+            Err(WebAuthnError::TrailingAuthData)
+        ));
+    }
+
+    #[test]
+    fn auth_data_without_attested_credential_data() {
+        let mut v = vec![0; 37];
+        v[32] = 0x01;
+        v[33..37].copy_from_slice(&7u32.to_be_bytes());
+        let decoded = DecodedAuthData::try_from(v.as_slice()).unwrap();
+        assert!(decoded.user_present);
+        assert_eq!(decoded.counter, 7);
+        assert!(decoded.attested_credential_data.is_none());
+    }
+
+    #[test]
+    fn backup_flags() {
+        let mut v = vec![0; 37];
+        for &(flags, eligible, state) in
+            &[(0, false, false), (BE, true, false), (BE | BS, true, true)]
+        {
+            v[32] = UP | flags;
+            let decoded = DecodedAuthData::try_from(v.as_slice()).unwrap();
+            assert_eq!(decoded.backup_eligible, eligible);
+            assert_eq!(decoded.backup_state, state);
+        }
+        // Only a credential that is backup eligible can be backed up.
+        v[32] = UP | BS;
+        assert!(matches!(
+            DecodedAuthData::try_from(v.as_slice()),
+            Err(WebAuthnError::InvalidBackupFlags)
+        ));
+    }
+
+    #[test]
+    fn credential_id_lengths() {
+        let cose = TestKey::es256().cose;
+        for &len in &[1, 16, 64, 1023] {
+            let attested = attested_credential_data([0; 16], &vec![1; len], &cose);
+            let decoded = DecodedAuthData::try_from(&auth_data(RP_ID, UP | AT, 0, &attested)[..])
+                .unwrap()
+                .attested_credential_data
+                .unwrap();
+            assert_eq!(decoded.credentialid_length as usize, len);
+            assert_eq!(decoded.credentialid, vec![1; len]);
+        }
+        for &len in &[0, 1024] {
+            let attested = attested_credential_data([0; 16], &vec![1; len], &cose);
+            assert!(matches!(
+                DecodedAuthData::try_from(&auth_data(RP_ID, UP | AT, 0, &attested)[..]),
+                Err(WebAuthnError::MalformedCredentialData)
+            ));
+        }
+    }
+
+    #[test]
+    fn truncated_attested_credential_data() {
+        // The AT flag is set, but the AAGUID and credential id length are cut off.
+        let mut v = vec![0; 37 + 10];
+        v[32] = 0x41;
+        assert!(matches!(
+            DecodedAuthData::try_from(v.as_slice()),
+            Err(WebAuthnError::AuthDataTooShort)
+        ));
+        // The credential id is shorter than its length says.
+        let mut v = vec![0; 37 + 18 + 4];
+        v[32] = 0x41;
+        v[37 + 16..37 + 18].copy_from_slice(&16u16.to_be_bytes());
+        assert!(matches!(
+            DecodedAuthData::try_from(v.as_slice()),
+            Err(WebAuthnError::MalformedCredentialData)
+        ));
+    }
+
+    #[test]
+    fn extensions_after_credential_public_key() {
+        let key = TestKey::es256();
+        let mut rest = attested_credential_data([0; 16], b"credential", &key.cose);
+        let extensions = cbor_map(vec![(text("credProtect"), serde_cbor::Value::U64(2))]);
+        rest.extend_from_slice(&serde_cbor::to_vec(&extensions).unwrap());
+        let v = auth_data(RP_ID, UP | AT | ED, 0, &rest);
+        let decoded = DecodedAuthData::try_from(v.as_slice()).unwrap();
+        let attested = decoded.attested_credential_data.unwrap();
+        assert_eq!(attested.credentialid, b"credential".to_vec());
+        assert_eq!(
+            attested.credential_public_key,
+            cose::CoseKey::try_from(key.cose.as_slice()).unwrap()
+        );
+        let extensions = decoded.extensions.unwrap();
+        assert_eq!(
+            extensions.get("credProtect"),
+            Some(&serde_cbor::Value::U64(2))
+        );
+    }
+
+    #[test]
+    fn assertion_extensions() {
+        let extensions = cbor_map(vec![(
+            text("hmac-secret"),
+            serde_cbor::Value::Bytes(vec![1; 32]),
+        )]);
+        let v = auth_data(RP_ID, UP | ED, 1, &serde_cbor::to_vec(&extensions).unwrap());
+        let decoded = DecodedAuthData::try_from(v.as_slice()).unwrap();
+        assert!(decoded.attested_credential_data.is_none());
+        assert_eq!(
+            decoded.extensions.unwrap().get("hmac-secret"),
+            Some(&serde_cbor::Value::Bytes(vec![1; 32]))
+        );
+    }
+
+    #[test]
+    fn aaguid_uuid_format() {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&hex("cb69481e8ff7403993ec0a2729a154a8"));
+        let aaguid = Aaguid(bytes);
+        assert_eq!(aaguid.to_string(), "cb69481e-8ff7-4039-93ec-0a2729a154a8");
+        assert_eq!(
+            "cb69481e-8ff7-4039-93ec-0a2729a154a8"
+                .parse::<Aaguid>()
+                .unwrap(),
+            aaguid
+        );
+        assert_eq!(
+            "CB69481E8FF7403993EC0A2729A154A8"
+                .parse::<Aaguid>()
+                .unwrap(),
+            aaguid
+        );
+        assert_eq!(
+            serde_json::to_string(&aaguid).unwrap(),
+            "\"cb69481e-8ff7-4039-93ec-0a2729a154a8\""
+        );
+        for invalid in &[
+            "cb69481e-8ff7-4039-93ec",
+            "cb69481e-8ff7-4039-93ec-0a2729a154ag",
+        ] {
+            assert!(matches!(
+                invalid.parse::<Aaguid>(),
+                Err(WebAuthnError::InvalidAaguid)
+            ));
+        }
+    }
+
+    // `JSON.stringify(credential)` of a registration in Chrome, with the binary members cut short.
+    const CHROME_REGISTRATION: &str = r#"{
+        "authenticatorAttachment": "cross-platform",
+        "clientExtensionResults": {"credProps": {"rk": false}},
+        "id": "5t4AQPvDd6vhQ1BRt0SJK1s7QxcxoVAfnmHGFJ5aV1Y",
+        "rawId": "5t4AQPvDd6vhQ1BRt0SJK1s7QxcxoVAfnmHGFJ5aV1Y",
+        "response": {
+            "attestationObject": "o2NmbXRkbm9uZWdhdHRTdG10oGhhdXRoRGF0YVjE",
+            "authenticatorData": "SZYN5YgOjGh0NBcPZHZgW4_krrmihjLHmVzzuoMdl2NBAAAAAQ",
+            "clientDataJSON": "eyJ0eXBlIjoid2ViYXV0aG4uY3JlYXRlIn0",
+            "publicKey": "MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE",
+            "publicKeyAlgorithm": -7,
+            "transports": ["hybrid", "internal"]
+        },
+        "type": "public-key"
+    }"#;
+
+    #[test]
+    fn browser_registration_json() {
+        let req: RegisterRequest = serde_json::from_str(CHROME_REGISTRATION).unwrap();
+        assert_eq!(
+            req.raw_id.as_base64url(),
+            "5t4AQPvDd6vhQ1BRt0SJK1s7QxcxoVAfnmHGFJ5aV1Y"
+        );
+        assert_eq!(
+            req.authenticator_attachment,
+            Some(AuthenticatorAttachment::CrossPlatform)
+        );
+        assert_eq!(
+            req.client_extension_results.cred_props.unwrap().rk,
+            Some(false)
+        );
+        assert_eq!(
+            req.response.transports,
+            vec![
+                AuthenticatorTransport::Hybrid,
+                AuthenticatorTransport::Internal
+            ]
+        );
+    }
+
+    // `JSON.stringify(credential)` of a discoverable credential login in Chrome, with the binary
+    // members cut short.
+    const CHROME_ASSERTION: &str = r#"{
+        "authenticatorAttachment": "platform",
+        "clientExtensionResults": {},
+        "id": "5t4AQPvDd6vhQ1BRt0SJK1s7QxcxoVAfnmHGFJ5aV1Y",
+        "rawId": "5t4AQPvDd6vhQ1BRt0SJK1s7QxcxoVAfnmHGFJ5aV1Y",
+        "response": {
+            "authenticatorData": "SZYN5YgOjGh0NBcPZHZgW4_krrmihjLHmVzzuoMdl2MdAAAAAA",
+            "clientDataJSON": "eyJ0eXBlIjoid2ViYXV0aG4uZ2V0In0",
+            "signature": "MEUCIQ",
+            "userHandle": "Y2Fyb2w"
+        },
+        "type": "public-key"
+    }"#;
+
+    #[test]
+    fn browser_assertion_json() {
+        let req: LoginRequest = serde_json::from_str(CHROME_ASSERTION).unwrap();
+        assert_eq!(req.type_, "public-key");
+        assert_eq!(
+            req.raw_id.as_base64url(),
+            "5t4AQPvDd6vhQ1BRt0SJK1s7QxcxoVAfnmHGFJ5aV1Y"
+        );
+        assert_eq!(req.response.user_handle.as_deref(), Some("Y2Fyb2w"));
+        // Security keys without discoverable credentials return no user handle.
+        let json = CHROME_ASSERTION.replace(r#""Y2Fyb2w""#, "null");
+        let req: LoginRequest = serde_json::from_str(&json).unwrap();
+        assert!(req.response.user_handle.is_none());
+    }
 }
@@ -0,0 +1,101 @@
+use crate::{ct_eq, Credential, CredentialId};
+use std::collections::HashMap;
+
+// Persistent storage for registered credentials, keyed by the user they belong to.
+pub trait CredentialStore {
+    // Stores `cred` for `user`, replacing any credential with the same id.
+    fn save(&mut self, user: &str, cred: &Credential);
+    // Replaces the stored credential with the same id as `cred`, e.g. to record a new counter or
+    // name.
+    fn update(&mut self, cred: &Credential);
+    fn find(&self, user: &str) -> Vec<Credential>;
+    // Every stored credential, with the user it belongs to.
+    fn find_all(&self) -> Vec<(String, Credential)>;
+    fn find_by_id(&self, id: &CredentialId) -> Option<Credential>;
+    // Like `find_by_id`, but also returns the user the credential belongs to. Used to find the
+    // user of a discoverable credential login.
+    fn find_credential(&self, id: &CredentialId) -> Option<(String, Credential)>;
+    // Deletes the credential with id `id` of `user`, and returns whether there was one.
+    fn remove(&mut self, user: &str, id: &CredentialId) -> bool;
+}
+
+// The default in-memory store.
+//
+// Each credential id is compared in constant time, so that the time a comparison takes does not
+// reveal how much of a guessed id matches a registered one. Only the comparisons are: lookups
+// stop at the first matching credential, so their total time still depends on where it is stored.
+// `benches/credential_lookup.rs` measures the cost against plain `==`.
+impl CredentialStore for HashMap<String, Vec<Credential>> {
+    fn save(&mut self, user: &str, cred: &Credential) {
+        let credentials = self.entry(user.to_string()).or_default();
+        match credentials
+            .iter_mut()
+            .find(|c| ct_eq(c.id.as_bytes(), cred.id.as_bytes()))
+        {
+            Some(c) => *c = cred.clone(),
+            None => credentials.push(cred.clone()),
+        }
+    }
+
+    fn update(&mut self, cred: &Credential) {
+        if let Some(c) = self
+            .values_mut()
+            .flatten()
+            .find(|c| ct_eq(c.id.as_bytes(), cred.id.as_bytes()))
+        {
+            *c = cred.clone();
+        }
+    }
+
+    fn find(&self, user: &str) -> Vec<Credential> {
+        self.get(user).cloned().unwrap_or_default()
+    }
+
+    fn find_all(&self) -> Vec<(String, Credential)> {
+        self.iter()
+            .flat_map(|(user, credentials)| {
+                credentials.iter().map(move |c| (user.clone(), c.clone()))
+            })
+            .collect()
+    }
+
+    fn find_by_id(&self, id: &CredentialId) -> Option<Credential> {
+        self.values()
+            .flatten()
+            .find(|c| ct_eq(c.id.as_bytes(), id.as_bytes()))
+            .cloned()
+    }
+
+    fn find_credential(&self, id: &CredentialId) -> Option<(String, Credential)> {
+        self.iter().find_map(|(user, credentials)| {
+            credentials
+                .iter()
+                .find(|c| ct_eq(c.id.as_bytes(), id.as_bytes()))
+                .map(|c| (user.clone(), c.clone()))
+        })
+    }
+
+    fn remove(&mut self, user: &str, id: &CredentialId) -> bool {
+        let credentials = match self.get_mut(user) {
+            Some(credentials) => credentials,
+            None => return false,
+        };
+        let len = credentials.len();
+        credentials.retain(|c| !ct_eq(c.id.as_bytes(), id.as_bytes()));
+        credentials.len() != len
+    }
+}
+
+// Like `CredentialStore`, for stores backed by e.g. an async database client. Used by the
+// `*_async` ceremonies of `WebAuthn`.
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+pub trait AsyncCredentialStore {
+    async fn save(&mut self, user: &str, cred: &Credential);
+    async fn update(&mut self, cred: &Credential);
+    async fn find(&self, user: &str) -> Vec<Credential>;
+    async fn find_all(&self) -> Vec<(String, Credential)>;
+    async fn find_by_id(&self, id: &CredentialId) -> Option<Credential>;
+    async fn find_credential(&self, id: &CredentialId) -> Option<(String, Credential)>;
+    async fn remove(&mut self, user: &str, id: &CredentialId) -> bool;
+}
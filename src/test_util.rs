@@ -0,0 +1,397 @@
+// Helpers shared by the unit tests, for building what browsers and authenticators send. Not
+// every test module uses all of them.
+#![allow(dead_code)]
+
+use crate::{requests, AssertionResult, CredentialStore, RegistrationResult, WebAuthn};
+use crate::{Challenge, WebAuthnError};
+use openssl::{asn1, bn, ec, hash, nid, pkey, rsa, sign, x509};
+use serde_cbor::{ObjectKey, Value};
+use std::collections::BTreeMap;
+
+pub const RP_ID: &str = "example.com";
+pub const ORIGIN: &str = "https://example.com";
+
+// Authenticator data flags.
+pub const UP: u8 = 0x01;
+pub const UV: u8 = 0x04;
+pub const BE: u8 = 0x08;
+pub const BS: u8 = 0x10;
+pub const AT: u8 = 0x40;
+pub const ED: u8 = 0x80;
+
+pub fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+pub fn b64(v: &[u8]) -> String {
+    crate::encode_base64url(v)
+}
+
+pub fn sha256(v: &[u8]) -> Vec<u8> {
+    openssl::sha::sha256(v).to_vec()
+}
+
+pub fn cbor_map(entries: Vec<(ObjectKey, Value)>) -> Value {
+    Value::Object(entries.into_iter().collect::<BTreeMap<_, _>>())
+}
+
+pub fn text(s: &str) -> ObjectKey {
+    ObjectKey::String(s.to_string())
+}
+
+// A credential key pair and its COSE_Key encoding.
+pub struct TestKey {
+    pub key: pkey::PKey<pkey::Private>,
+    pub cose: Vec<u8>,
+    pub alg: i64,
+}
+
+impl TestKey {
+    pub fn es256() -> Self {
+        TestKey::ec(nid::Nid::X9_62_PRIME256V1, crate::cose::ALG_ES256)
+    }
+
+    pub fn ec(curve: nid::Nid, alg: i64) -> Self {
+        let (crv, len) = match curve {
+            nid::Nid::X9_62_PRIME256V1 => (crate::cose::EC2_CURVE_P256, 32),
+            nid::Nid::SECP384R1 => (crate::cose::EC2_CURVE_P384, 48),
+            _ => (crate::cose::EC2_CURVE_P521, 66),
+        };
+        let group = ec::EcGroup::from_curve_name(curve).unwrap();
+        let ec_key = ec::EcKey::generate(&group).unwrap();
+        let mut ctx = bn::BigNumContext::new().unwrap();
+        let mut x = bn::BigNum::new().unwrap();
+        let mut y = bn::BigNum::new().unwrap();
+        ec_key
+            .public_key()
+            .affine_coordinates_gfp(&group, &mut x, &mut y, &mut ctx)
+            .unwrap();
+        let cose = cbor_map(vec![
+            (ObjectKey::Integer(1), Value::I64(crate::cose::KEY_TYPE_EC2)),
+            (ObjectKey::Integer(3), Value::I64(alg)),
+            (ObjectKey::Integer(-1), Value::I64(crv)),
+            (
+                ObjectKey::Integer(-2),
+                Value::Bytes(x.to_vec_padded(len).unwrap()),
+            ),
+            (
+                ObjectKey::Integer(-3),
+                Value::Bytes(y.to_vec_padded(len).unwrap()),
+            ),
+        ]);
+        TestKey {
+            key: pkey::PKey::from_ec_key(ec_key).unwrap(),
+            cose: serde_cbor::to_vec(&cose).unwrap(),
+            alg,
+        }
+    }
+
+    pub fn ed25519() -> Self {
+        let key = pkey::PKey::generate_ed25519().unwrap();
+        let cose = cbor_map(vec![
+            (ObjectKey::Integer(1), Value::I64(crate::cose::KEY_TYPE_OKP)),
+            (ObjectKey::Integer(3), Value::I64(crate::cose::ALG_EDDSA)),
+            (
+                ObjectKey::Integer(-1),
+                Value::I64(crate::cose::OKP_CURVE_ED25519),
+            ),
+            (
+                ObjectKey::Integer(-2),
+                Value::Bytes(key.raw_public_key().unwrap()),
+            ),
+        ]);
+        TestKey {
+            key,
+            cose: serde_cbor::to_vec(&cose).unwrap(),
+            alg: crate::cose::ALG_EDDSA,
+        }
+    }
+
+    pub fn rsa(alg: i64) -> Self {
+        let rsa_key = rsa::Rsa::generate(2048).unwrap();
+        let cose = cbor_map(vec![
+            (ObjectKey::Integer(1), Value::I64(crate::cose::KEY_TYPE_RSA)),
+            (ObjectKey::Integer(3), Value::I64(alg)),
+            (ObjectKey::Integer(-1), Value::Bytes(rsa_key.n().to_vec())),
+            (ObjectKey::Integer(-2), Value::Bytes(rsa_key.e().to_vec())),
+        ]);
+        TestKey {
+            key: pkey::PKey::from_rsa(rsa_key).unwrap(),
+            cose: serde_cbor::to_vec(&cose).unwrap(),
+            alg,
+        }
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+        if self.alg == crate::cose::ALG_EDDSA {
+            let mut signer = sign::Signer::new_without_digest(&self.key).unwrap();
+            return signer.sign_oneshot_to_vec(data).unwrap();
+        }
+        let digest = crate::cose::digest(self.alg).unwrap();
+        let mut signer = sign::Signer::new(digest, &self.key).unwrap();
+        let pss = [
+            crate::cose::ALG_PS256,
+            crate::cose::ALG_PS384,
+            crate::cose::ALG_PS512,
+        ];
+        if pss.contains(&self.alg) {
+            signer.set_rsa_padding(rsa::Padding::PKCS1_PSS).unwrap();
+            signer
+                .set_rsa_pss_saltlen(sign::RsaPssSaltlen::DIGEST_LENGTH)
+                .unwrap();
+        }
+        signer.update(data).unwrap();
+        signer.sign_to_vec().unwrap()
+    }
+}
+
+// Encodes a DER element with `tag` around `contents`.
+pub fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut v = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        v.push(len as u8);
+    } else {
+        v.push(0x82);
+        v.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+    v.extend_from_slice(contents);
+    v
+}
+
+pub const CA_NAME: &str = "Test CA";
+
+// A certificate for `key` with the given subject entries and DER encoded extensions, issued by
+// `issuer` under the name `CA_NAME`.
+pub fn certificate(
+    key: &TestKey,
+    subject: &[(&str, &str)],
+    issuer: &TestKey,
+    extensions: &[(&str, Vec<u8>)],
+) -> x509::X509 {
+    let mut subject_name = x509::X509NameBuilder::new().unwrap();
+    for (field, value) in subject {
+        subject_name.append_entry_by_text(field, value).unwrap();
+    }
+    let mut issuer_name = x509::X509NameBuilder::new().unwrap();
+    issuer_name.append_entry_by_text("CN", CA_NAME).unwrap();
+    let mut builder = x509::X509Builder::new().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_subject_name(&subject_name.build()).unwrap();
+    builder.set_issuer_name(&issuer_name.build()).unwrap();
+    builder.set_pubkey(&key.key).unwrap();
+    let not_before = asn1::Asn1Time::days_from_now(0).unwrap();
+    let not_after = asn1::Asn1Time::days_from_now(30).unwrap();
+    builder.set_not_before(&not_before).unwrap();
+    builder.set_not_after(&not_after).unwrap();
+    for (oid, value) in extensions {
+        let oid = asn1::Asn1Object::from_str(oid).unwrap();
+        let value = asn1::Asn1OctetString::new_from_bytes(value).unwrap();
+        let extension = x509::X509Extension::new_from_der(&oid, false, &value).unwrap();
+        builder.append_extension(extension).unwrap();
+    }
+    builder
+        .sign(&issuer.key, hash::MessageDigest::sha256())
+        .unwrap();
+    builder.build()
+}
+
+// A self-signed CA certificate for `key`, named `CA_NAME`.
+pub fn root_certificate(key: &TestKey) -> x509::X509 {
+    let basic_constraints = der(0x30, &der(0x01, &[0xff]));
+    certificate(
+        key,
+        &[("CN", CA_NAME)],
+        key,
+        &[("2.5.29.19", basic_constraints)],
+    )
+}
+
+pub fn x5c(chain: &[&x509::X509]) -> Value {
+    Value::Array(
+        chain
+            .iter()
+            .map(|cert| Value::Bytes(cert.to_der().unwrap()))
+            .collect(),
+    )
+}
+
+// A JWS in compact serialization of `payload`, signed with the ES256 `key` of the first
+// certificate of `chain`.
+pub fn es256_jws(key: &TestKey, chain: &[&x509::X509], payload: &serde_json::Value) -> String {
+    let x5c = chain
+        .iter()
+        .map(|cert| base64::encode(&cert.to_der().unwrap()))
+        .collect::<Vec<_>>();
+    let header = serde_json::json!({ "alg": "ES256", "x5c": x5c });
+    let signing_input = format!(
+        "{}.{}",
+        b64(header.to_string().as_bytes()),
+        b64(payload.to_string().as_bytes())
+    );
+    // JWS signatures are the raw concatenation of r and s.
+    let signature =
+        openssl::ecdsa::EcdsaSig::from_der(&key.sign(signing_input.as_bytes())).unwrap();
+    let mut raw = signature.r().to_vec_padded(32).unwrap();
+    raw.extend_from_slice(&signature.s().to_vec_padded(32).unwrap());
+    format!("{}.{}", signing_input, b64(&raw))
+}
+
+// A metadata BLOB with an entry for each AAGUID, whose model is attested under `root`. The BLOB
+// itself is signed with a throwaway key, so load it with `TrustAnchorStore::no_trust_required()`.
+pub fn metadata_blob(entries: &[(&str, &x509::X509)]) -> String {
+    let entries = entries
+        .iter()
+        .map(|(aaguid, root)| {
+            serde_json::json!({
+                "aaguid": aaguid,
+                "metadataStatement": {
+                    "description": format!("Authenticator {}", aaguid),
+                    "authenticatorVersion": 1,
+                    "protocolFamily": "fido2",
+                    "attestationRootCertificates": [base64::encode(&root.to_der().unwrap())],
+                },
+                "statusReports": [{ "status": "FIDO_CERTIFIED_L1" }],
+            })
+        })
+        .collect::<Vec<_>>();
+    let payload = serde_json::json!({
+        "legalHeader": "",
+        "no": 1,
+        "nextUpdate": "2030-01-01",
+        "entries": entries,
+    });
+    let signer = TestKey::es256();
+    let cert = certificate(&signer, &[("CN", "Metadata")], &signer, &[]);
+    es256_jws(&signer, &[&cert], &payload)
+}
+
+// The attested credential data of a new credential, to be appended to the authenticator data.
+pub fn attested_credential_data(aaguid: [u8; 16], credential_id: &[u8], cose: &[u8]) -> Vec<u8> {
+    let mut v = aaguid.to_vec();
+    v.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+    v.extend_from_slice(credential_id);
+    v.extend_from_slice(cose);
+    v
+}
+
+pub fn auth_data(rp_id: &str, flags: u8, counter: u32, rest: &[u8]) -> Vec<u8> {
+    let mut v = sha256(rp_id.as_bytes());
+    v.push(flags);
+    v.extend_from_slice(&counter.to_be_bytes());
+    v.extend_from_slice(rest);
+    v
+}
+
+pub fn client_data(type_: &str, challenge: &Challenge) -> Vec<u8> {
+    format!(
+        r#"{{"type":"{}","challenge":"{}","origin":"{}"}}"#,
+        type_,
+        challenge.as_base64url(),
+        ORIGIN
+    )
+    .into_bytes()
+}
+
+pub fn attestation_object(fmt: &str, att_stmt: Value, auth_data: &[u8]) -> Vec<u8> {
+    let object = cbor_map(vec![
+        (text("fmt"), Value::String(fmt.to_string())),
+        (text("attStmt"), att_stmt),
+        (text("authData"), Value::Bytes(auth_data.to_vec())),
+    ]);
+    serde_cbor::to_vec(&object).unwrap()
+}
+
+pub fn none_attestation(auth_data: &[u8]) -> Vec<u8> {
+    attestation_object("none", cbor_map(Vec::new()), auth_data)
+}
+
+pub fn register_request(
+    credential_id: &[u8],
+    client_data: &[u8],
+    attestation_object: &[u8],
+) -> requests::RegisterRequest {
+    serde_json::from_value(serde_json::json!({
+        "id": b64(credential_id),
+        "rawId": b64(credential_id),
+        "type": "public-key",
+        "response": {
+            "attestationObject": b64(attestation_object),
+            "clientDataJSON": b64(client_data),
+        },
+    }))
+    .unwrap()
+}
+
+pub fn login_request(
+    credential_id: &[u8],
+    auth_data: &[u8],
+    client_data: &[u8],
+    signature: &[u8],
+) -> requests::LoginRequest {
+    serde_json::from_value(serde_json::json!({
+        "id": b64(credential_id),
+        "rawId": b64(credential_id),
+        "type": "public-key",
+        "response": {
+            "authenticatorData": b64(auth_data),
+            "clientDataJSON": b64(client_data),
+            "signature": b64(signature),
+        },
+    }))
+    .unwrap()
+}
+
+// Registers a credential for `user` with `none` attestation.
+pub fn register<S: CredentialStore>(
+    w: &mut WebAuthn<S>,
+    user: &str,
+    key: &TestKey,
+    credential_id: &[u8],
+) -> Result<RegistrationResult, WebAuthnError> {
+    register_with_flags(w, user, key, credential_id, UP)
+}
+
+// Like `register`, with authenticator data carrying `flags` besides AT.
+pub fn register_with_flags<S: CredentialStore>(
+    w: &mut WebAuthn<S>,
+    user: &str,
+    key: &TestKey,
+    credential_id: &[u8],
+    flags: u8,
+) -> Result<RegistrationResult, WebAuthnError> {
+    let challenge = w.generate_challenge(user.to_string()).unwrap();
+    let attested = attested_credential_data([0; 16], credential_id, &key.cose);
+    let auth_data = auth_data(RP_ID, flags | AT, 0, &attested);
+    w.register(&register_request(
+        credential_id,
+        &client_data("webauthn.create", &challenge),
+        &none_attestation(&auth_data),
+    ))
+}
+
+// Logs `user` in with a credential registered through `register`, with authenticator data
+// carrying `flags` and `counter`.
+pub fn login<S: CredentialStore>(
+    w: &mut WebAuthn<S>,
+    user: &str,
+    key: &TestKey,
+    credential_id: &[u8],
+    flags: u8,
+    counter: u32,
+) -> Result<AssertionResult, WebAuthnError> {
+    let options = w.request_options(user.to_string(), None).unwrap();
+    let auth_data = auth_data(RP_ID, flags, counter, &[]);
+    let client_data = client_data("webauthn.get", &options.challenge);
+    let mut signed = auth_data.clone();
+    signed.extend_from_slice(&sha256(&client_data));
+    w.verify_assertion(&login_request(
+        credential_id,
+        &auth_data,
+        &client_data,
+        &key.sign(&signed),
+    ))
+}
@@ -0,0 +1,146 @@
+// Parsing of the TPM 2.0 structures in "tpm" attestation statements.
+// See https://trustedcomputinggroup.org/resource/tpm-library-specification/ (part 2, structures)
+
+use crate::WebAuthnError;
+use byteorder::ByteOrder;
+
+pub const TPM_ALG_RSA: u16 = 0x0001;
+pub const TPM_ALG_SHA1: u16 = 0x0004;
+pub const TPM_ALG_SHA256: u16 = 0x000b;
+pub const TPM_ALG_ECC: u16 = 0x0023;
+pub const TPM_ECC_NIST_P256: u16 = 0x0003;
+pub const TPM_ECC_NIST_P384: u16 = 0x0004;
+pub const TPM_ECC_NIST_P521: u16 = 0x0005;
+// Marks structures created by the TPM itself, see TPM_GENERATED_VALUE.
+const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+const TPM_ST_ATTEST_CERTIFY: u16 = 0x8017;
+
+// Reads big-endian TPM fields from the front of a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], WebAuthnError> {
+        if self.0.len() < n {
+            return Err(WebAuthnError::InvalidAttestationStatement);
+        }
+        let (bytes, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, WebAuthnError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, WebAuthnError> {
+        Ok(byteorder::BigEndian::read_u16(self.bytes(2)?))
+    }
+
+    fn u32(&mut self) -> Result<u32, WebAuthnError> {
+        Ok(byteorder::BigEndian::read_u32(self.bytes(4)?))
+    }
+
+    fn u64(&mut self) -> Result<u64, WebAuthnError> {
+        Ok(byteorder::BigEndian::read_u64(self.bytes(8)?))
+    }
+
+    // A TPM2B structure: a 16 bit size followed by that many bytes.
+    fn sized(&mut self) -> Result<&'a [u8], WebAuthnError> {
+        let size = self.u16()? as usize;
+        self.bytes(size)
+    }
+
+    fn end(&self) -> Result<(), WebAuthnError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(WebAuthnError::InvalidAttestationStatement)
+        }
+    }
+}
+
+// The key of a TPMT_PUBLIC area.
+#[derive(Debug)]
+pub enum PublicKey<'a> {
+    // The exponent is 0 for the default of 65537.
+    Rsa {
+        exponent: u32,
+        modulus: &'a [u8],
+    },
+    Ecc {
+        curve: u16,
+        x: &'a [u8],
+        y: &'a [u8],
+    },
+}
+
+// TPMT_PUBLIC, the public area of the credential key.
+#[derive(Debug)]
+pub struct PubArea<'a> {
+    pub name_alg: u16,
+    pub key: PublicKey<'a>,
+}
+
+impl<'a> PubArea<'a> {
+    pub fn parse(v: &'a [u8]) -> Result<Self, WebAuthnError> {
+        let mut r = Reader(v);
+        let type_ = r.u16()?;
+        let name_alg = r.u16()?;
+        let _object_attributes = r.u32()?;
+        let _auth_policy = r.sized()?;
+        let key = match type_ {
+            TPM_ALG_RSA => {
+                let _symmetric = r.u16()?;
+                let _scheme = r.u16()?;
+                let _key_bits = r.u16()?;
+                let exponent = r.u32()?;
+                let modulus = r.sized()?;
+                PublicKey::Rsa { exponent, modulus }
+            }
+            TPM_ALG_ECC => {
+                let _symmetric = r.u16()?;
+                let _scheme = r.u16()?;
+                let curve = r.u16()?;
+                let _kdf = r.u16()?;
+                let x = r.sized()?;
+                let y = r.sized()?;
+                PublicKey::Ecc { curve, x, y }
+            }
+            _ => return Err(WebAuthnError::UnsupportedKeyType),
+        };
+        r.end()?;
+        Ok(PubArea { name_alg, key })
+    }
+}
+
+// TPMS_ATTEST of type TPM_ST_ATTEST_CERTIFY, which the attestation identity key signs.
+#[derive(Debug)]
+pub struct CertInfo<'a> {
+    pub extra_data: &'a [u8],
+    // The TPM name of the certified key: its name algorithm followed by its hash.
+    pub attested_name: &'a [u8],
+}
+
+impl<'a> CertInfo<'a> {
+    pub fn parse(v: &'a [u8]) -> Result<Self, WebAuthnError> {
+        let mut r = Reader(v);
+        if r.u32()? != TPM_GENERATED_VALUE || r.u16()? != TPM_ST_ATTEST_CERTIFY {
+            return Err(WebAuthnError::InvalidAttestationStatement);
+        }
+        let _qualified_signer = r.sized()?;
+        let extra_data = r.sized()?;
+        // TPMS_CLOCK_INFO: clock, resetCount, restartCount and safe.
+        let _clock = r.u64()?;
+        let _reset_count = r.u32()?;
+        let _restart_count = r.u32()?;
+        let _safe = r.u8()?;
+        let _firmware_version = r.u64()?;
+        let attested_name = r.sized()?;
+        let _attested_qualified_name = r.sized()?;
+        r.end()?;
+        Ok(CertInfo {
+            extra_data,
+            attested_name,
+        })
+    }
+}
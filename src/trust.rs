@@ -0,0 +1,127 @@
+use crate::WebAuthnError;
+use openssl::{stack, x509};
+
+// The root certificates that attestation certificate chains have to lead up to.
+//
+// A store created with `new()` requires every attestation to be trusted: x5c chains have to
+// validate up to one of the registered roots, and attestations without a chain (`none` and self
+// attestation) are rejected. `no_trust_required()`, the default of `WebAuthn`, still checks that
+// each certificate of a chain is signed by the next one, but accepts any root and unattested
+// credentials. With metadata set on `WebAuthn`, chains additionally have to validate up to the
+// roots that the metadata lists for the attested authenticator model, see
+// `MetadataService::trust_anchors`.
+//
+//     let mut trust_anchors = TrustAnchorStore::new();
+//     trust_anchors.add_root_pem(include_bytes!("yubico-u2f-ca.pem"))?;
+//     webauthn.set_trust_anchors(trust_anchors);
+#[derive(Debug, Clone)]
+pub struct TrustAnchorStore {
+    roots: Vec<x509::X509>,
+    trust_required: bool,
+}
+
+impl Default for TrustAnchorStore {
+    fn default() -> Self {
+        TrustAnchorStore::no_trust_required()
+    }
+}
+
+impl TrustAnchorStore {
+    pub fn new() -> Self {
+        TrustAnchorStore {
+            roots: Vec::new(),
+            trust_required: true,
+        }
+    }
+
+    pub fn no_trust_required() -> Self {
+        TrustAnchorStore {
+            roots: Vec::new(),
+            trust_required: false,
+        }
+    }
+
+    pub fn add_root(&mut self, root: x509::X509) {
+        self.roots.push(root);
+    }
+
+    pub fn add_root_der(&mut self, der: &[u8]) -> Result<(), WebAuthnError> {
+        let root = x509::X509::from_der(der).map_err(WebAuthnError::OpenSSL)?;
+        self.add_root(root);
+        Ok(())
+    }
+
+    // Adds every certificate of a PEM file, which may hold more than one.
+    pub fn add_root_pem(&mut self, pem: &[u8]) -> Result<(), WebAuthnError> {
+        let roots = x509::X509::stack_from_pem(pem).map_err(WebAuthnError::OpenSSL)?;
+        self.roots.extend(roots);
+        Ok(())
+    }
+
+    pub fn roots(&self) -> &[x509::X509] {
+        &self.roots
+    }
+
+    pub fn set_trust_required(&mut self, trust_required: bool) {
+        self.trust_required = trust_required;
+    }
+
+    pub fn trust_required(&self) -> bool {
+        self.trust_required
+    }
+
+    // Checks an attestation certificate chain, leaf first. The chain may or may not include the
+    // root itself.
+    pub fn verify_chain(&self, chain: &[x509::X509]) -> Result<(), WebAuthnError> {
+        if !self.trust_required {
+            return verify_signatures(chain);
+        }
+        let (leaf, intermediates) = chain
+            .split_first()
+            .ok_or(WebAuthnError::InvalidAttestationStatement)?;
+        let mut store = x509::store::X509StoreBuilder::new().map_err(WebAuthnError::OpenSSL)?;
+        for root in &self.roots {
+            store
+                .add_cert(root.clone())
+                .map_err(WebAuthnError::OpenSSL)?;
+        }
+        let store = store.build();
+        let mut untrusted = stack::Stack::new().map_err(WebAuthnError::OpenSSL)?;
+        for cert in intermediates {
+            untrusted
+                .push(cert.clone())
+                .map_err(WebAuthnError::OpenSSL)?;
+        }
+        let mut context = x509::X509StoreContext::new().map_err(WebAuthnError::OpenSSL)?;
+        let error = context
+            .init(&store, leaf, &untrusted, |c| {
+                Ok(if c.verify_cert()? {
+                    None
+                } else {
+                    Some(c.error())
+                })
+            })
+            .map_err(WebAuthnError::OpenSSL)?;
+        match error {
+            None => Ok(()),
+            Some(error) => {
+                debug!("attestation chain is not trusted: {}", error);
+                Err(WebAuthnError::UntrustedAttestation)
+            }
+        }
+    }
+}
+
+// Checks that each certificate of the chain is signed by the one following it.
+fn verify_signatures(chain: &[x509::X509]) -> Result<(), WebAuthnError> {
+    for pair in chain.windows(2) {
+        let issuer_key = pair[1].public_key().map_err(WebAuthnError::OpenSSL)?;
+        if !pair[0]
+            .verify(&issuer_key)
+            .map_err(WebAuthnError::OpenSSL)?
+        {
+            return Err(WebAuthnError::InvalidAttestationSignature);
+        }
+    }
+    Ok(())
+}